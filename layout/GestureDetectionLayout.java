@@ -0,0 +1,215 @@
+// Generated by `cargo run -- --generate-layout`. Do not edit.
+package com.example.myapp;
+
+public final class GestureDetectionLayout {
+    private GestureDetectionLayout() {}
+
+    public static final int PROTOCOL_VERSION = 20;
+    public static final int MAGIC = 0x54534547;
+    public static final int SEGMENT_LENGTH = 4096;
+    public static final int HEADER_LENGTH = 448;
+    public static final int POINT_SIZE = 40;
+    public static final int POINT_CAPACITY = 91;
+
+    public static final int MAGIC_OFFSET = 0;
+    public static final int MAGIC_SIZE = 4;
+    public static final int PROTOCOL_VERSION_OFFSET = 4;
+    public static final int PROTOCOL_VERSION_SIZE = 4;
+    public static final int HEADER_LENGTH_OFFSET = 8;
+    public static final int HEADER_LENGTH_SIZE = 4;
+    public static final int SEGMENT_LENGTH_OFFSET = 12;
+    public static final int SEGMENT_LENGTH_SIZE = 4;
+    public static final int POINT_CAPACITY_OFFSET = 16;
+    public static final int POINT_CAPACITY_SIZE = 4;
+    public static final int IS_ALIVE_OFFSET = 24;
+    public static final int IS_ALIVE_SIZE = 8;
+    public static final int SERVER_STATE_OFFSET = 32;
+    public static final int SERVER_STATE_SIZE = 8;
+    public static final int CLIENT_REQUEST_OFFSET = 40;
+    public static final int CLIENT_REQUEST_SIZE = 8;
+    public static final int DURATION_OFFSET = 48;
+    public static final int DURATION_SIZE = 8;
+    public static final int FLING_DIRECTION_OFFSET = 56;
+    public static final int FLING_DIRECTION_SIZE = 8;
+    public static final int POINT_ARRAY_SIZE_OFFSET = 64;
+    public static final int POINT_ARRAY_SIZE_SIZE = 8;
+    public static final int RESULT_OFFSET = 72;
+    public static final int RESULT_SIZE = 8;
+    public static final int POINT_ARRAY_START_OFFSET = 80;
+    public static final int POINT_ARRAY_START_SIZE = 8;
+    public static final int COMMAND_HEAD_OFFSET = 88;
+    public static final int COMMAND_HEAD_SIZE = 8;
+    public static final int COMMAND_TAIL_OFFSET = 96;
+    public static final int COMMAND_TAIL_SIZE = 8;
+    public static final int PROVISIONAL_RESULT_OFFSET = 104;
+    public static final int PROVISIONAL_RESULT_SIZE = 8;
+    public static final int RESULT_PARAMETER_OFFSET = 112;
+    public static final int RESULT_PARAMETER_SIZE = 8;
+    public static final int TEMPLATE_ID_OFFSET = 120;
+    public static final int TEMPLATE_ID_SIZE = 8;
+    public static final int TEMPLATE_NAME_OFFSET = 128;
+    public static final int TEMPLATE_NAME_SIZE = 16;
+    public static final int DIRECTION_ANGLE_OFFSET = 144;
+    public static final int DIRECTION_ANGLE_SIZE = 8;
+    public static final int DIRECTION_DEAD_ZONE_OFFSET = 152;
+    public static final int DIRECTION_DEAD_ZONE_SIZE = 8;
+    public static final int DIRECTION_BOUNDARY_POLICY_OFFSET = 160;
+    public static final int DIRECTION_BOUNDARY_POLICY_SIZE = 8;
+    public static final int DETECTOR_OFFSET = 168;
+    public static final int DETECTOR_SIZE = 8;
+    public static final int HOLD_DURATION_OFFSET = 176;
+    public static final int HOLD_DURATION_SIZE = 8;
+    public static final int CIRCLE_RADIUS_OFFSET = 184;
+    public static final int CIRCLE_RADIUS_SIZE = 8;
+    public static final int DRAG_LENGTH_OFFSET = 192;
+    public static final int DRAG_LENGTH_SIZE = 8;
+    public static final int DISPLAY_DENSITY_OFFSET = 200;
+    public static final int DISPLAY_DENSITY_SIZE = 8;
+    public static final int DISPLAY_WIDTH_OFFSET = 208;
+    public static final int DISPLAY_WIDTH_SIZE = 8;
+    public static final int DISPLAY_HEIGHT_OFFSET = 216;
+    public static final int DISPLAY_HEIGHT_SIZE = 8;
+    public static final int TAP_INTERVAL_OFFSET = 224;
+    public static final int TAP_INTERVAL_SIZE = 8;
+    public static final int TAP_DISTANCE_OFFSET = 232;
+    public static final int TAP_DISTANCE_SIZE = 8;
+    public static final int COMPASS_MODE_OFFSET = 240;
+    public static final int COMPASS_MODE_SIZE = 8;
+    public static final int RESULT_GESTURE_TYPE_OFFSET = 248;
+    public static final int RESULT_GESTURE_TYPE_SIZE = 8;
+    public static final int RESULT_DIRECTION_OFFSET = 256;
+    public static final int RESULT_DIRECTION_SIZE = 8;
+    public static final int RESULT_ALTERNATIVE_DIRECTION_OFFSET = 264;
+    public static final int RESULT_ALTERNATIVE_DIRECTION_SIZE = 8;
+    public static final int RESULT_ROTATION_OFFSET = 272;
+    public static final int RESULT_ROTATION_SIZE = 8;
+    public static final int RESULT_MEASUREMENT_OFFSET = 280;
+    public static final int RESULT_MEASUREMENT_SIZE = 8;
+    public static final int RESULT_TAPS_OFFSET = 288;
+    public static final int RESULT_TAPS_SIZE = 8;
+    public static final int RESULT_CONFIDENCE_OFFSET = 296;
+    public static final int RESULT_CONFIDENCE_SIZE = 8;
+    public static final int RESULT_ERROR_CODE_OFFSET = 304;
+    public static final int RESULT_ERROR_CODE_SIZE = 8;
+    public static final int RESULT_MIN_X_OFFSET = 312;
+    public static final int RESULT_MIN_X_SIZE = 8;
+    public static final int RESULT_MIN_Y_OFFSET = 320;
+    public static final int RESULT_MIN_Y_SIZE = 8;
+    public static final int RESULT_MAX_X_OFFSET = 328;
+    public static final int RESULT_MAX_X_SIZE = 8;
+    public static final int RESULT_MAX_Y_OFFSET = 336;
+    public static final int RESULT_MAX_Y_SIZE = 8;
+    public static final int RESULT_CENTROID_X_OFFSET = 344;
+    public static final int RESULT_CENTROID_X_SIZE = 8;
+    public static final int RESULT_CENTROID_Y_OFFSET = 352;
+    public static final int RESULT_CENTROID_Y_SIZE = 8;
+    public static final int RESULT_PATH_LENGTH_OFFSET = 360;
+    public static final int RESULT_PATH_LENGTH_SIZE = 8;
+    public static final int RESULT_TEMPLATE_ID_OFFSET = 368;
+    public static final int RESULT_TEMPLATE_ID_SIZE = 8;
+    public static final int CANDIDATES_OFFSET = 376;
+    public static final int CANDIDATES_SIZE = 64;
+    public static final int COMPASS_SECTOR_OFFSET = 440;
+    public static final int COMPASS_SECTOR_SIZE = 8;
+    public static final int POINTS_OFFSET = 448;
+    public static final int POINTS_SIZE = 3640;
+
+    public static final long IS_ALIVE = 1L;
+    public static final long NOT_ALIVE = 0L;
+    public static final long CLICK_INDEX_BITWISE_OFFSET = 0L;
+    public static final long BOOMERANG_BITWISE_OFFSET = 2L;
+    public static final long SWIPE_BITWISE_OFFSET = 10L;
+    public static final long CIRCLE_BITWISE_OFFSET = 18L;
+    public static final long ERROR_BITWISE_OFFSET = 20L;
+    public static final long PINCH_BITWISE_OFFSET = 21L;
+    public static final long SPREAD_BITWISE_OFFSET = 22L;
+    public static final long TWO_FINGER_ROTATION_BITWISE_OFFSET = 23L;
+    public static final long MULTI_SWIPE_BITWISE_OFFSET = 25L;
+    public static final long DOUBLE_TAP_BITWISE_OFFSET = 33L;
+    public static final long TRIPLE_TAP_BITWISE_OFFSET = 34L;
+    public static final long HOLD_DRAG_BITWISE_OFFSET = 35L;
+    public static final long TEMPLATE_BITWISE_OFFSET = 43L;
+    public static final long NO_TEMPLATE = -1L;
+    public static final long TEMPLATE_NAME_LENGTH = 16L;
+    public static final long TEMPLATE_ENTRY_SIZE = 24L;
+    public static final long TEMPLATE_LIST_CAPACITY = 152L;
+    public static final long DISQUALIFICATION_ENTRY_SIZE = 32L;
+    public static final long DISQUALIFICATION_LIST_CAPACITY = 114L;
+    public static final long DISQUALIFICATION_RULE_OFFSET = 0L;
+    public static final long DISQUALIFICATION_GESTURES_OFFSET = 8L;
+    public static final long DISQUALIFICATION_MEASURED_OFFSET = 16L;
+    public static final long DISQUALIFICATION_THRESHOLD_OFFSET = 24L;
+    public static final long PROVISIONAL_CONFIDENCE_BITWISE_OFFSET = 48L;
+    public static final long PROVISIONAL_CONFIDENCE_SCALE = 1000L;
+    public static final long POINT_X_OFFSET = 0L;
+    public static final long POINT_Y_OFFSET = 8L;
+    public static final long POINT_TIMESTAMP_OFFSET = 16L;
+    public static final long POINT_PRESSURE_OFFSET = 24L;
+    public static final long POINT_POINTER_ID_OFFSET = 32L;
+    public static final long COMMAND_SLOT_SIZE = 48L;
+    public static final long COMMAND_RING_CAPACITY = 76L;
+    public static final long GESTURE_TYPE_CLICK = 0L;
+    public static final long GESTURE_TYPE_HOLD = 1L;
+    public static final long GESTURE_TYPE_SWIPE = 2L;
+    public static final long GESTURE_TYPE_CIRCLE = 3L;
+    public static final long GESTURE_TYPE_BOOMERANG = 4L;
+    public static final long GESTURE_TYPE_PINCH = 5L;
+    public static final long GESTURE_TYPE_SPREAD = 6L;
+    public static final long GESTURE_TYPE_ROTATE = 7L;
+    public static final long GESTURE_TYPE_MULTI_SWIPE = 8L;
+    public static final long GESTURE_TYPE_HOLD_DRAG = 9L;
+    public static final long NO_ORDINAL = -1L;
+    public static final long CANDIDATE_COUNT = 4L;
+    public static final long CANDIDATE_ENTRY_SIZE = 16L;
+    public static final long CANDIDATE_GESTURE_TYPE_OFFSET = 0L;
+    public static final long CANDIDATE_SCORE_OFFSET = 8L;
+    public static final long ERROR_CODE_INVALID_POINT_ARRAY = 1L;
+    public static final long ERROR_CODE_MISSING_POINTS = 2L;
+    public static final long RULE_POINTER_COUNT = 0L;
+    public static final long RULE_DRAG_LENGTH = 1L;
+    public static final long RULE_HOLD_DURATION = 2L;
+    public static final long RULE_HOLD_BEFORE_DRAG = 3L;
+    public static final long RULE_CIRCLE_RADIUS = 4L;
+    public static final long RULE_REVERSAL = 5L;
+    public static final long RULE_BOOMERANG_RETURN = 6L;
+    public static final long RULE_CIRCLE_TURNING = 7L;
+    public static final long RULE_SWIPE_TURNING = 8L;
+    public static final long RULE_MULTI_SWIPE_DISTANCE = 9L;
+    public static final long RULE_ROTATION_ANGLE = 10L;
+    public static final long RULE_SCALE_FACTOR = 11L;
+    public static final long RULE_MULTI_TOUCH_MOVEMENT = 12L;
+    public static final long BOUNDARY_POLICY_NEAREST = 0L;
+    public static final long BOUNDARY_POLICY_AMBIGUOUS = 1L;
+    public static final long BOUNDARY_POLICY_CARDINAL = 2L;
+    public static final long COMPASS_MODE_EIGHT_POINT = 0L;
+    public static final long COMPASS_MODE_SIXTEEN_POINT = 1L;
+    public static final long DETECTOR_RULE_BASED = 0L;
+    public static final long DETECTOR_TEMPLATE_BASED = 1L;
+    public static final long DETECTOR_DUMMY = 2L;
+    public static final long SERVER_STATE_WAITING_FOR_COMMAND = 0L;
+    public static final long SERVER_STATE_WAITING_FOR_ARGS = 1L;
+    public static final long SERVER_STATE_DETECTING_GESTURE = 2L;
+    public static final long SERVER_STATE_PING_ACKNOWLEDGED = 3L;
+    public static final long SERVER_STATE_DETECTION_COMPLETE = 4L;
+    public static final long SERVER_STATE_EXITED = 5L;
+    public static final long SERVER_STATE_STREAMING = 6L;
+    public static final long SERVER_STATE_TEMPLATE_REQUEST_COMPLETE = 7L;
+    public static final long SERVER_STATE_CONFIGURATION_COMPLETE = 8L;
+    public static final long SERVER_STATE_DEBUG_REQUEST_COMPLETE = 9L;
+    public static final long CLIENT_REQUEST_EMPTY = 0L;
+    public static final long CLIENT_REQUEST_PING = 1L;
+    public static final long CLIENT_REQUEST_BEGIN = 2L;
+    public static final long CLIENT_REQUEST_DETECT = 3L;
+    public static final long CLIENT_REQUEST_CLEAR_ACKNOWLEDGEMENT = 4L;
+    public static final long CLIENT_REQUEST_HALT = 5L;
+    public static final long CLIENT_REQUEST_STREAM = 6L;
+    public static final long CLIENT_REQUEST_REGISTER_TEMPLATE = 7L;
+    public static final long CLIENT_REQUEST_REMOVE_TEMPLATE = 8L;
+    public static final long CLIENT_REQUEST_LIST_TEMPLATES = 9L;
+    public static final long CLIENT_REQUEST_CONFIGURE = 10L;
+    public static final long CLIENT_REQUEST_LIST_DISQUALIFICATIONS = 11L;
+    public static final long CLIENT_COMMAND_COLLECT = 1L;
+    public static final long CLIENT_COMMAND_INSERT = 2L;
+    public static final long CLIENT_COMMAND_DETECT = 3L;
+    public static final long CLIENT_COMMAND_RESET = 4L;
+}
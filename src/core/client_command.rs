@@ -1,22 +1,94 @@
+use crate::core::detection_system::GestureDetectionIPC;
 use crate::core::direction::Direction;
 use crate::core::point::Point;
 
+/// One entry of the command ring in shared memory: the command's ordinal followed by its
+/// arguments, all stored as f64s. `COLLECT` and `INSERT` store the point's x, y, timestamp,
+/// pressure and pointer id; `DETECT` stores the duration, the point count and the fling
+/// direction (-1 if unknown).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CommandSlot {
+    pub(crate) command: i64, // offset=0, +8
+    pub(crate) arguments: [f64; 5], // offset=8, +40
+}
+
+/// Commands the client streams to the server through the command ring while the server is in
+/// the `Streaming` state, so that points can be sent as the finger moves.
+#[derive(Debug, Clone, Copy)]
 pub(crate) enum ClientCommand {
+    /// Start a new gesture at its first point, discarding any points collected before. Further
+    /// fingers join the gesture through `Insert` with their own pointer id.
     Collect{ point: Point },
+    /// Append a point to the gesture being collected.
     Insert{ point: Point },
+    /// Detect the collected gesture. `count` is the number of points sent since `Collect`, so
+    /// that the server can tell a complete gesture from one that lost points.
     Detect {
         duration: u64,
-        count: u8,
+        count: u32,
         /* fits in thi u8 primitive because we have exactly 8 directions */
         fling_direction: Option<Direction>,
     },
+    /// Discard the gesture being collected.
     Reset,
 }
 
 impl ClientCommand {
-    /// Retrieve the next command from the client by reading the first 64 bits (8 bytes) from the shared memory.
-    pub(crate) fn retrieve_next_command() {
-        // Read the first 64 bits from the shared memory
-        // and interpret them as a ClientCommand
+    pub(crate) fn java_ordinal(&self) -> i64 {
+        match self {
+            ClientCommand::Collect { .. } => 1,
+            ClientCommand::Insert { .. } => 2,
+            ClientCommand::Detect { .. } => 3,
+            ClientCommand::Reset => 4,
+        }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        String::from(match self {
+            ClientCommand::Collect { .. } => "COLLECT",
+            ClientCommand::Insert { .. } => "INSERT",
+            ClientCommand::Detect { .. } => "DETECT",
+            ClientCommand::Reset => "RESET",
+        })
+    }
+
+    pub(crate) fn to_slot(self) -> CommandSlot {
+        let arguments = match self {
+            ClientCommand::Collect { point } | ClientCommand::Insert { point } => point.to_fields(),
+            ClientCommand::Detect { duration, count, fling_direction } => [
+                duration as f64,
+                count as f64,
+                fling_direction.map_or(-1.0, |direction| direction.java_ordinal() as f64),
+                0.0,
+                0.0,
+            ],
+            ClientCommand::Reset => [0.0; 5],
+        };
+        CommandSlot { command: self.java_ordinal(), arguments }
     }
-}
\ No newline at end of file
+
+    pub(crate) fn from_slot(slot: &CommandSlot) -> Result<Self, String> {
+        let arguments = slot.arguments;
+        match slot.command {
+            1 => Ok(ClientCommand::Collect { point: Point::from_fields(arguments) }),
+            2 => Ok(ClientCommand::Insert { point: Point::from_fields(arguments) }),
+            3 => Ok(ClientCommand::Detect {
+                duration: arguments[0] as u64,
+                count: arguments[1] as u32,
+                fling_direction: match arguments[2] {
+                    direction if (0.0..8.0).contains(&direction) => Some(Direction::from_index(direction as usize)),
+                    _ => None,
+                },
+            }),
+            4 => Ok(ClientCommand::Reset),
+            command => Err(format!("Unknown command {}", command)),
+        }
+    }
+
+    /// Retrieve the next command the client has published to the command ring in the shared
+    /// memory, or None once the server has caught up with the client.
+    pub(crate) fn retrieve_next_command(ipc: &GestureDetectionIPC) -> Option<Result<Self, String>> {
+        ipc.pop_command_slot().map(|slot| Self::from_slot(&slot))
+    }
+}
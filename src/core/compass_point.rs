@@ -0,0 +1,82 @@
+/// The 16 points of the compass, for clients that need a finer heading than the 8 sectors of
+/// `Direction`. Like `Direction`, they go anticlockwise from East, with North up the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompassPoint {
+    East,
+    EastNortheast,
+    Northeast,
+    NorthNortheast,
+    North,
+    NorthNorthwest,
+    Northwest,
+    WestNorthwest,
+    West,
+    WestSouthwest,
+    Southwest,
+    SouthSouthwest,
+    South,
+    SouthSoutheast,
+    Southeast,
+    EastSoutheast,
+}
+
+impl CompassPoint {
+    pub(crate) fn name(self) -> String {
+        String::from(
+            match self {
+                CompassPoint::East => "EAST",
+                CompassPoint::EastNortheast => "EAST_NORTH_EAST",
+                CompassPoint::Northeast => "NORTH_EAST",
+                CompassPoint::NorthNortheast => "NORTH_NORTH_EAST",
+                CompassPoint::North => "NORTH",
+                CompassPoint::NorthNorthwest => "NORTH_NORTH_WEST",
+                CompassPoint::Northwest => "NORTH_WEST",
+                CompassPoint::WestNorthwest => "WEST_NORTH_WEST",
+                CompassPoint::West => "WEST",
+                CompassPoint::WestSouthwest => "WEST_SOUTH_WEST",
+                CompassPoint::Southwest => "SOUTH_WEST",
+                CompassPoint::SouthSouthwest => "SOUTH_SOUTH_WEST",
+                CompassPoint::South => "SOUTH",
+                CompassPoint::SouthSoutheast => "SOUTH_SOUTH_EAST",
+                CompassPoint::Southeast => "SOUTH_EAST",
+                CompassPoint::EastSoutheast => "EAST_SOUTH_EAST",
+            }
+        )
+    }
+
+    pub(crate) fn java_ordinal(self) -> u32 {
+        Self::values().iter().position(|&point| point == self).unwrap() as u32
+    }
+
+    pub(crate) fn from_index(index: usize) -> CompassPoint {
+        Self::values().get(index).copied().unwrap_or(CompassPoint::East)
+    }
+
+    /// The compass point whose 22.5 degree sector contains `alpha`, an angle in degrees as
+    /// returned by `Direction::angle_from`. East covers -11.25 to 11.25 degrees.
+    pub(crate) fn from_alpha(alpha: f64) -> CompassPoint {
+        let sector = 360.0 / 16.0;
+        Self::from_index(((alpha.rem_euclid(360.0) + sector / 2.0) / sector) as usize % 16)
+    }
+
+    pub(crate) fn values() -> Vec<Self> {
+        vec![
+            Self::East,
+            Self::EastNortheast,
+            Self::Northeast,
+            Self::NorthNortheast,
+            Self::North,
+            Self::NorthNorthwest,
+            Self::Northwest,
+            Self::WestNorthwest,
+            Self::West,
+            Self::WestSouthwest,
+            Self::Southwest,
+            Self::SouthSouthwest,
+            Self::South,
+            Self::SouthSoutheast,
+            Self::Southeast,
+            Self::EastSoutheast,
+        ]
+    }
+}
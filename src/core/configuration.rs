@@ -0,0 +1,109 @@
+use crate::core::direction::CompassMode;
+use crate::core::direction_sectors::{BoundaryPolicy, DirectionSectors};
+use crate::core::display::Display;
+use crate::core::gesture_detector::TEMPLATE_BASED_DETECTOR;
+use crate::core::tap_sequencer::TapThresholds;
+use crate::core::thresholds::Thresholds;
+
+/// Settings of the detector that clients can change at runtime with the CONFIGURE request, as
+/// stored in the segment. The server validates the whole block before applying any of it, and
+/// writes back the settings in effect afterwards.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ConfigurationBlock {
+    /// Degrees on either side of a direction sector boundary, see `DirectionSectors`.
+    pub(crate) direction_dead_zone: f64, // offset=0, +8
+    /// `BoundaryPolicy` ordinal.
+    pub(crate) direction_boundary_policy: i64, // offset=8, +8
+    /// Id of the registered `GestureDetector` to classify gestures with.
+    pub(crate) detector: i64, // offset=16, +8
+    /// Milliseconds a touch has to last to be a hold, see `Thresholds`.
+    pub(crate) hold_duration: i64, // offset=24, +8
+    /// Density-independent pixels a stroke has to circle its centroid at to be a circle.
+    pub(crate) circle_radius: f64, // offset=32, +8
+    /// Density-independent pixels a touch has to move to be a drag.
+    pub(crate) drag_length: f64, // offset=40, +8
+    /// Dots per inch of the client's display, see `Display`.
+    pub(crate) display_density: f64, // offset=48, +8
+    /// Width of the client's display in pixels, or 0 if it is not known.
+    pub(crate) display_width: f64, // offset=56, +8
+    /// Height of the client's display in pixels, or 0 if it is not known.
+    pub(crate) display_height: f64, // offset=64, +8
+    /// Milliseconds between the ends of two clicks for them to be taps of one sequence, or 0 to
+    /// report every click as a click, see `TapThresholds`.
+    pub(crate) tap_interval: i64, // offset=72, +8
+    /// Density-independent pixels a tap may land from the first tap of its sequence.
+    pub(crate) tap_distance: f64, // offset=80, +8
+    /// `CompassMode` ordinal: whether `compass_sector` counts 8 or 16 sectors.
+    pub(crate) compass_mode: i64, // offset=88, +8
+}
+
+/// The settings a `ConfigurationBlock` describes, once validated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Configuration {
+    pub(crate) direction_sectors: DirectionSectors,
+    /// Checked against the registered detectors by `GestureDetectionServer::set_configuration`.
+    pub(crate) detector: u32,
+    pub(crate) thresholds: Thresholds,
+    pub(crate) display: Display,
+    pub(crate) tap_thresholds: TapThresholds,
+    pub(crate) compass_mode: CompassMode,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            direction_sectors: DirectionSectors::default(),
+            detector: TEMPLATE_BASED_DETECTOR,
+            thresholds: Thresholds::default(),
+            display: Display::default(),
+            tap_thresholds: TapThresholds::default(),
+            compass_mode: CompassMode::default(),
+        }
+    }
+}
+
+impl Configuration {
+    pub(crate) fn to_block(self) -> ConfigurationBlock {
+        ConfigurationBlock {
+            direction_dead_zone: self.direction_sectors.dead_zone(),
+            direction_boundary_policy: self.direction_sectors.boundary_policy().java_ordinal(),
+            detector: self.detector as i64,
+            hold_duration: self.thresholds.hold_duration() as i64,
+            circle_radius: self.thresholds.circle_radius(),
+            drag_length: self.thresholds.drag_length(),
+            display_density: self.display.density(),
+            display_width: self.display.width(),
+            display_height: self.display.height(),
+            tap_interval: self.tap_thresholds.maximum_interval.as_millis() as i64,
+            tap_distance: self.tap_thresholds.maximum_distance,
+            compass_mode: self.compass_mode.java_ordinal(),
+        }
+    }
+
+    pub(crate) fn from_block(block: &ConfigurationBlock) -> Result<Self, String> {
+        let boundary_policy = BoundaryPolicy::from_java_ordinal(block.direction_boundary_policy)
+            .ok_or_else(|| format!("Unknown boundary policy {}", block.direction_boundary_policy))?;
+        let thresholds = Thresholds::new(block.hold_duration as i128, block.circle_radius, block.drag_length)?;
+        let display = Display::new(block.display_density, block.display_width, block.display_height)?;
+        let tap_thresholds = TapThresholds::new(block.tap_interval, block.tap_distance)?;
+        let compass_mode = CompassMode::from_java_ordinal(block.compass_mode)
+            .ok_or_else(|| format!("Unknown compass mode {}", block.compass_mode))?;
+        if let Some(diagonal) = display.diagonal().filter(|&diagonal| thresholds.drag_length() > diagonal) {
+            return Err(format!(
+                "Drag length {}dp ({:.1}mm) does not fit on a display {:.0}dp across",
+                thresholds.drag_length(),
+                display.millimetres(thresholds.drag_length()),
+                diagonal
+            ));
+        }
+        Ok(Configuration {
+            direction_sectors: DirectionSectors::new(block.direction_dead_zone, boundary_policy)?,
+            detector: u32::try_from(block.detector).map_err(|_| format!("Unknown detector {}", block.detector))?,
+            thresholds,
+            display,
+            tap_thresholds,
+            compass_mode,
+        })
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use crate::core::direction::Direction;
+use crate::core::direction_result::DirectionResult;
+use crate::core::disqualification::Rule;
+use crate::core::generic_gesture_type::GenericGestureType;
+use crate::core::gesture_detection::GestureDetection;
+use crate::core::point::Point;
+use crate::core::rotation_direction::RotationDirection;
+
+/// The fingers have to spread apart or close in by at least this factor for a pinch or spread.
+const MINIMUM_SCALE_FACTOR: f64 = 1.25;
+/// The fingers have to turn around their centroid by at least this many degrees for a rotation.
+const MINIMUM_ROTATION_ANGLE: f64 = 20f64;
+/// In a multi-finger swipe, every finger's heading may differ from the group's by at most this.
+const MAXIMUM_SWIPE_HEADING_DEVIATION: f64 = 45f64;
+
+/// Group the touch points by pointer id, keeping each pointer's points in the order they arrived.
+pub(crate) fn pointer_tracks(touch_points: &[Point]) -> BTreeMap<u32, Vec<Point>> {
+    let mut tracks = BTreeMap::<u32, Vec<Point>>::new();
+    for point in touch_points {
+        tracks.entry(point.pointer_id).or_default().push(*point);
+    }
+    tracks
+}
+
+/// Rule-based classifier for gestures made with two or more fingers, comparing where the fingers
+/// started with where they ended. Like the single-pointer rules, it disqualifies gestures from
+/// `possible_gestures` and stores the matching `DirectionResult`:
+/// - `MultiSwipe` when every finger travels the same way, with the direction and finger count
+/// - `Rotate` when the fingers turn around their centroid, with the rotation and angle in degrees
+/// - `Pinch` or `Spread` when they close in or move apart, with the factor their spread changed by
+/// - otherwise a multi-finger `Click` or `Hold`, depending on the duration
+pub(crate) fn determine_gesture_multi_touch(
+    tracks: &BTreeMap<u32, Vec<Point>>,
+    gesture_detection: &mut GestureDetection,
+) -> (GenericGestureType, Option<DirectionResult>) {
+    let starts = tracks.values().filter_map(|track| track.first().copied()).collect::<Vec<_>>();
+    let ends = tracks.values().filter_map(|track| track.last().copied()).collect::<Vec<_>>();
+    let fingers = starts.len();
+    let thresholds = gesture_detection.thresholds;
+
+    gesture_detection.remove(
+        GenericGestureType::Swipe.java_ordinal()
+            | GenericGestureType::Circle.java_ordinal()
+            | GenericGestureType::Boomerang.java_ordinal()
+            | GenericGestureType::HoldDrag.java_ordinal(),
+        Rule::PointerCount,
+        fingers as f64,
+        2.0,
+    );
+
+    let (start_centroid, end_centroid) = (Point::centroid(&starts), Point::centroid(&ends));
+    let translation = end_centroid.distance_to(&start_centroid);
+    let scale = match Point::mean_distance(&starts, &start_centroid) {
+        start_spread if start_spread > 0.0 => Point::mean_distance(&ends, &end_centroid) / start_spread,
+        _ => 1.0,
+    };
+    let rotation = starts
+        .iter()
+        .zip(&ends)
+        .map(|(start, end)| {
+            Direction::normalize_degrees(Direction::angle_from(*end, end_centroid) - Direction::angle_from(*start, start_centroid))
+        })
+        .sum::<f64>() / fingers as f64;
+    let heading = Direction::angle_from(end_centroid, start_centroid);
+    let moved_together = starts.iter().zip(&ends).all(|(start, end)| {
+        start.distance_to(end) >= thresholds.drag_length()
+            && Direction::normalize_degrees(Direction::angle_from(*end, *start) - heading).abs() <= MAXIMUM_SWIPE_HEADING_DEVIATION
+    });
+
+    // How far each measurement is past its threshold, so that the strongest movement wins
+    let scale_strength = scale.ln().abs() / MINIMUM_SCALE_FACTOR.ln();
+    let rotation_strength = rotation.abs() / MINIMUM_ROTATION_ANGLE;
+
+    let mut direction_angle = None;
+    let direction_result = if translation >= thresholds.drag_length() && moved_together {
+        gesture_detection.remove(
+            !GenericGestureType::MultiSwipe.java_ordinal(),
+            Rule::MultiSwipeDistance,
+            translation,
+            thresholds.drag_length(),
+        );
+        direction_angle = Some(heading);
+        // A multi-finger swipe has a single direction, so an ambiguous heading takes the nearer one
+        match gesture_detection.direction_sectors.drag(heading, gesture_detection.provisional_direction()) {
+            DirectionResult::Drag(direction) | DirectionResult::AmbiguousDrag(direction, _) => {
+                DirectionResult::MultiDrag(direction, fingers as u8)
+            }
+            _ => DirectionResult::MultiDrag(Direction::direction_from_alpha(heading), fingers as u8),
+        }
+    } else if rotation_strength >= 1.0 && rotation_strength >= scale_strength {
+        gesture_detection.remove(
+            !GenericGestureType::Rotate.java_ordinal(),
+            Rule::RotationAngle,
+            rotation,
+            MINIMUM_ROTATION_ANGLE,
+        );
+        let rotation_direction = if rotation < 0.0 { RotationDirection::Clockwise } else { RotationDirection::AntiClockwise };
+        DirectionResult::Rotation(rotation_direction, rotation.abs())
+    } else if scale_strength >= 1.0 {
+        let gesture = if scale < 1.0 { GenericGestureType::Pinch } else { GenericGestureType::Spread };
+        gesture_detection.remove(
+            !gesture.java_ordinal(),
+            Rule::ScaleFactor,
+            scale,
+            MINIMUM_SCALE_FACTOR,
+        );
+        DirectionResult::Scale(scale)
+    } else {
+        gesture_detection.remove(
+            GenericGestureType::MultiSwipe.java_ordinal()
+                | GenericGestureType::Rotate.java_ordinal()
+                | GenericGestureType::Pinch.java_ordinal()
+                | GenericGestureType::Spread.java_ordinal(),
+            Rule::MultiTouchMovement,
+            scale_strength.max(rotation_strength),
+            1.0,
+        );
+        if gesture_detection.duration < thresholds.hold_duration() {
+            gesture_detection.remove(
+                GenericGestureType::Hold.java_ordinal(),
+                Rule::HoldDuration,
+                gesture_detection.duration as f64,
+                thresholds.hold_duration() as f64,
+            );
+        } else {
+            gesture_detection.remove(
+                GenericGestureType::Click.java_ordinal(),
+                Rule::HoldDuration,
+                gesture_detection.duration as f64,
+                thresholds.hold_duration() as f64,
+            );
+        }
+        DirectionResult::None
+    };
+
+    let (generic_gesture_type, _) = gesture_detection.result();
+    gesture_detection.direction_result = direction_result;
+    gesture_detection.direction_angle = direction_angle;
+    match direction_result {
+        DirectionResult::None => (generic_gesture_type, None),
+        direction_result => (generic_gesture_type, Some(direction_result)),
+    }
+}
@@ -0,0 +1,271 @@
+use crate::core::detect_gesture_multi_touch::{determine_gesture_multi_touch, pointer_tracks};
+use crate::core::direction::Direction;
+use crate::core::direction_result::DirectionResult;
+use crate::core::disqualification::Rule;
+use crate::core::generic_gesture_type::GenericGestureType;
+use crate::core::gesture_detection::GestureDetection;
+use crate::core::point::Point;
+use crate::core::rotation_direction::RotationDirection;
+
+/// Segments shorter than this (in dp) are treated as jitter when measuring how a stroke turns.
+const MINIMUM_SEGMENT_LENGTH: f64 = 10f64;
+/// Segments shorter than this fraction of the whole path are treated as jitter as well.
+const MINIMUM_SEGMENT_LENGTH_RATIO: f64 = 0.02;
+/// A swipe may bend by at most this many degrees in total.
+const MAXIMUM_SWIPE_TURNING: f64 = 70f64;
+/// A stroke turning smoothly by at least this many degrees in total is a circle.
+const MINIMUM_CIRCLE_TURNING: f64 = 180f64;
+/// A single turn of at least this many degrees is a reversal (boomerang or looped circle).
+const MINIMUM_REVERSAL_ANGLE: f64 = 120f64;
+/// How far (in degrees) the return leg of a reversal may stray from retracing the outbound leg
+/// before the stroke is considered a loop instead of a boomerang.
+const MAXIMUM_BOOMERANG_RETURN_DEVIATION: f64 = 20f64;
+/// While holding before a drag, the finger may wander this far (in dp) from the first point.
+const MAXIMUM_HOLD_DRIFT: f64 = 20f64;
+
+/// Rule-based classifier. Each rule disqualifies gestures from `possible_gestures`, and whatever
+/// survives is the result. The matching `DirectionResult` is stored on the detection as well.
+/// Gestures made with more than one finger are handed to `determine_gesture_multi_touch`.
+pub fn determine_gesture_rule_based(
+    touch_points: &mut Vec<Point>,
+    gesture_detection: &mut GestureDetection,
+) -> (GenericGestureType, Option<DirectionResult>) {
+    let tracks = pointer_tracks(touch_points);
+    if tracks.len() > 1 {
+        return determine_gesture_multi_touch(&tracks, gesture_detection);
+    }
+    gesture_detection.remove(
+        GenericGestureType::Pinch.java_ordinal()
+            | GenericGestureType::Spread.java_ordinal()
+            | GenericGestureType::Rotate.java_ordinal()
+            | GenericGestureType::MultiSwipe.java_ordinal(),
+        Rule::PointerCount,
+        1.0,
+        2.0,
+    );
+
+    let thresholds = gesture_detection.thresholds;
+    let start = touch_points.first().copied().unwrap_or(Point::new(0.0, 0.0));
+    let end = touch_points.last().copied().unwrap_or(start);
+    let reach = touch_points.iter().map(|point| point.distance_to(&start)).fold(0f64, f64::max);
+
+    if reach < thresholds.drag_length() {
+        gesture_detection.remove(
+            GenericGestureType::Swipe.java_ordinal()
+                | GenericGestureType::Circle.java_ordinal()
+                | GenericGestureType::Boomerang.java_ordinal()
+                | GenericGestureType::HoldDrag.java_ordinal(),
+            Rule::DragLength,
+            reach,
+            thresholds.drag_length(),
+        );
+        if gesture_detection.duration < thresholds.hold_duration() {
+            gesture_detection.remove(
+                GenericGestureType::Hold.java_ordinal(),
+                Rule::HoldDuration,
+                gesture_detection.duration as f64,
+                thresholds.hold_duration() as f64,
+            );
+        } else {
+            gesture_detection.remove(
+                GenericGestureType::Click.java_ordinal(),
+                Rule::HoldDuration,
+                gesture_detection.duration as f64,
+                thresholds.hold_duration() as f64,
+            );
+        }
+        return finish(gesture_detection, None, None, DirectionResult::None);
+    }
+
+    gesture_detection.remove(
+        GenericGestureType::Click.java_ordinal() | GenericGestureType::Hold.java_ordinal(),
+        Rule::DragLength,
+        reach,
+        thresholds.drag_length(),
+    );
+
+    match held_for(touch_points) {
+        Some((held, departure)) if held >= thresholds.hold_duration() as f64 => {
+            gesture_detection.remove(
+                GenericGestureType::Swipe.java_ordinal()
+                    | GenericGestureType::Circle.java_ordinal()
+                    | GenericGestureType::Boomerang.java_ordinal(),
+                Rule::HoldBeforeDrag,
+                held,
+                thresholds.hold_duration() as f64,
+            );
+            // The drag starts from the last point of the hold, and heads the same way a swipe would
+            let drag = &touch_points[departure - 1..];
+            let drag = Direction::angle_from(point_along(drag, Point::path_length(drag) / 2.0), drag[0]);
+            return finish(gesture_detection, Some(drag), None, DirectionResult::None);
+        }
+        Some((held, _)) => gesture_detection.remove(
+            GenericGestureType::HoldDrag.java_ordinal(),
+            Rule::HoldBeforeDrag,
+            held,
+            thresholds.hold_duration() as f64,
+        ),
+        None => gesture_detection.remove(
+            GenericGestureType::HoldDrag.java_ordinal(),
+            Rule::HoldBeforeDrag,
+            f64::NAN,
+            thresholds.hold_duration() as f64,
+        ),
+    }
+
+    let radius = Point::mean_distance(touch_points, &Point::centroid(touch_points));
+    if radius < thresholds.circle_radius() {
+        gesture_detection.remove(
+            GenericGestureType::Circle.java_ordinal(),
+            Rule::CircleRadius,
+            radius,
+            thresholds.circle_radius(),
+        );
+    }
+
+    let path_length = Point::path_length(touch_points);
+    let turns = turning_angles(touch_points, path_length);
+    let total_turning: f64 = turns.iter().map(|&(_, angle)| angle).sum();
+    let sharpest_turn = turns.iter().copied().max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()));
+
+    let rotation = DirectionResult::Circular(
+        if total_turning < 0.0 { RotationDirection::Clockwise } else { RotationDirection::AntiClockwise }
+    );
+    let swipe = Direction::angle_from(point_along(touch_points, path_length / 2.0), start);
+
+    match sharpest_turn {
+        Some((apex, angle)) if angle.abs() >= MINIMUM_REVERSAL_ANGLE => {
+            let apex = touch_points[apex];
+            let outbound = Direction::angle_from(apex, start);
+            let inbound = Direction::angle_from(end, apex);
+            let deviation = (180.0 - Direction::normalize_degrees(inbound - outbound).abs()).abs();
+
+            gesture_detection.remove(
+                GenericGestureType::Swipe.java_ordinal(),
+                Rule::Reversal,
+                angle,
+                MINIMUM_REVERSAL_ANGLE,
+            );
+            if deviation <= MAXIMUM_BOOMERANG_RETURN_DEVIATION {
+                gesture_detection.remove(
+                    GenericGestureType::Circle.java_ordinal(),
+                    Rule::BoomerangReturn,
+                    deviation,
+                    MAXIMUM_BOOMERANG_RETURN_DEVIATION,
+                );
+            } else if gesture_detection.is_possible(GenericGestureType::Circle) {
+                gesture_detection.remove(
+                    GenericGestureType::Boomerang.java_ordinal(),
+                    Rule::BoomerangReturn,
+                    deviation,
+                    MAXIMUM_BOOMERANG_RETURN_DEVIATION,
+                );
+            }
+
+            // Average the outbound leg with the reversed return leg to get the boomerang's heading
+            let heading = outbound + Direction::normalize_degrees(inbound + 180.0 - outbound) / 2.0;
+            finish(gesture_detection, Some(swipe), Some((heading + 360.0) % 360.0), rotation)
+        }
+        _ => {
+            if total_turning.abs() >= MINIMUM_CIRCLE_TURNING && gesture_detection.is_possible(GenericGestureType::Circle) {
+                gesture_detection.remove(
+                    GenericGestureType::Swipe.java_ordinal() | GenericGestureType::Boomerang.java_ordinal(),
+                    Rule::CircleTurning,
+                    total_turning,
+                    MINIMUM_CIRCLE_TURNING,
+                );
+            } else if total_turning.abs() <= MAXIMUM_SWIPE_TURNING {
+                gesture_detection.remove(
+                    GenericGestureType::Circle.java_ordinal() | GenericGestureType::Boomerang.java_ordinal(),
+                    Rule::SwipeTurning,
+                    total_turning,
+                    MAXIMUM_SWIPE_TURNING,
+                );
+            } else {
+                gesture_detection.remove(
+                    GenericGestureType::Swipe.java_ordinal() | GenericGestureType::Circle.java_ordinal(),
+                    Rule::SwipeTurning,
+                    total_turning,
+                    MAXIMUM_SWIPE_TURNING,
+                );
+            }
+
+            // A hooked stroke heads towards the point where it has done half of its turning
+            let mut accumulated = 0f64;
+            let bend = turns
+                .iter()
+                .find(|&&(_, angle)| {
+                    accumulated += angle;
+                    accumulated.abs() >= total_turning.abs() / 2.0
+                })
+                .map_or(end, |&(index, _)| touch_points[index]);
+            finish(gesture_detection, Some(swipe), Some(Direction::angle_from(bend, start)), rotation)
+        }
+    }
+}
+
+/// Pick the surviving gesture and store the direction result that belongs to it. `swipe` and
+/// `boomerang` are the headings in degrees the gesture would have as a swipe or a boomerang,
+/// bucketed into directions by the detection's `direction_sectors`.
+fn finish(
+    gesture_detection: &mut GestureDetection,
+    swipe: Option<f64>,
+    boomerang: Option<f64>,
+    rotation: DirectionResult,
+) -> (GenericGestureType, Option<DirectionResult>) {
+    let (generic_gesture_type, _) = gesture_detection.result();
+    gesture_detection.direction_angle = match generic_gesture_type {
+        GenericGestureType::Swipe | GenericGestureType::HoldDrag => swipe,
+        GenericGestureType::Boomerang => boomerang,
+        _ => None,
+    };
+    gesture_detection.direction_result = match generic_gesture_type {
+        GenericGestureType::Circle => rotation,
+        _ => gesture_detection.direction_angle.map_or(DirectionResult::None, |angle| {
+            gesture_detection.direction_sectors.drag(angle, gesture_detection.provisional_direction())
+        }),
+    };
+    match gesture_detection.direction_result {
+        DirectionResult::None => (generic_gesture_type, None),
+        direction_result => (generic_gesture_type, Some(direction_result)),
+    }
+}
+
+/// How long, in milliseconds, the finger stayed within `MAXIMUM_HOLD_DRIFT` of the first point,
+/// and the index of the first point outside of it. None if the finger never left or the points
+/// that tell are not timestamped.
+fn held_for(touch_points: &[Point]) -> Option<(f64, usize)> {
+    let start = touch_points.first()?;
+    let departure = touch_points.iter().position(|point| point.distance_to(start) > MAXIMUM_HOLD_DRIFT)?;
+    Some((touch_points[departure].timestamp? - start.timestamp?, departure))
+}
+
+/// The point `distance` along the path, interpolated between touch points.
+fn point_along(touch_points: &[Point], distance: f64) -> Point {
+    let mut travelled = 0f64;
+    for pair in touch_points.windows(2) {
+        let length = pair[0].distance_to(&pair[1]);
+        if length > 0.0 && travelled + length >= distance {
+            return pair[0].add(pair[1].sub(pair[0]).mul((distance - travelled) / length));
+        }
+        travelled += length;
+    }
+    touch_points.last().copied().unwrap_or(Point::new(0.0, 0.0))
+}
+
+/// Signed heading change (anti-clockwise positive) between consecutive significant segments,
+/// paired with the index of the touch point where the later segment starts.
+fn turning_angles(touch_points: &[Point], path_length: f64) -> Vec<(usize, f64)> {
+    let minimum_length = MINIMUM_SEGMENT_LENGTH.max(path_length * MINIMUM_SEGMENT_LENGTH_RATIO);
+    let headings = touch_points
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0].distance_to(&pair[1]) >= minimum_length)
+        .map(|(index, pair)| (index, Direction::angle_from(pair[1], pair[0])))
+        .collect::<Vec<_>>();
+
+    headings
+        .windows(2)
+        .map(|pair| (pair[1].0, Direction::normalize_degrees(pair[1].1 - pair[0].1)))
+        .collect()
+}
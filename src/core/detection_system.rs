@@ -1,15 +1,29 @@
-use crate::core::detect_gesture_dummy::determine_gesture_dummy;
-use crate::core::direction::Direction;
+use crate::core::client_command::{ClientCommand, CommandSlot};
+use crate::core::configuration::{Configuration, ConfigurationBlock};
+use crate::core::direction::{CompassMode, Direction};
 use crate::core::direction_result::DirectionResult;
+use crate::core::direction_sectors::BoundaryPolicy;
+use crate::core::disqualification::{Disqualification, DisqualificationEntry, Rule};
+use crate::core::doorbell::Doorbell;
 use crate::core::generic_gesture_type::GenericGestureType;
 use crate::core::gesture_detection::GestureDetection;
+use crate::core::gesture_detector::{DummyDetector, GestureDetector, RuleBasedDetector, TemplateDetector, DUMMY_DETECTOR, RULE_BASED_DETECTOR, TEMPLATE_BASED_DETECTOR};
+use crate::core::ipc_header::{GestureDetectionIPCHeader, HeaderError, MAGIC, PROTOCOL_VERSION};
+use crate::core::ipc_layout::{Layout, LayoutField};
 use crate::core::point::Point;
+use crate::core::result_record::{decode_candidates, encode_candidates, CandidateEntry, DetectionError, GestureResult, ResultRecord, CANDIDATE_COUNT, NO_ORDINAL};
 use crate::core::rotation_direction::RotationDirection;
-use crate::core::detection_system::ClientRequest::{Begin, ClearAcknowledgement, Detect, Halt, Ping, Uninitialized};
-use crate::core::detection_system::ServerState::{DetectionComplete, DetectingGesture, WaitingForArgs, PingAcknowledged, Exited, WaitingForCommand};
+use crate::core::shared_memory::{SharedMemorySegment, SHARED_MEMORY_NAME, SHARED_MEMORY_SIZE};
+use crate::core::tap_sequencer::{TapSequencer, TapTime};
+use crate::core::template_recognizer::{decode_name, encode_name, TemplateEntry, TemplateRecognizer, TEMPLATE_NAME_LENGTH};
+use crate::core::detection_system::ClientRequest::{Begin, ClearAcknowledgement, Configure, Detect, Halt, ListDisqualifications, ListTemplates, Ping, RegisterTemplate, RemoveTemplate, Stream, Uninitialized};
+use crate::core::detection_system::ServerState::{ConfigurationComplete, DebugRequestComplete, DetectionComplete, DetectingGesture, WaitingForArgs, PingAcknowledged, Exited, Streaming, TemplateRequestComplete, WaitingForCommand};
+use std::cell::UnsafeCell;
 use std::ffi::c_void;
-use std::ptr;
-use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::mem::offset_of;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::{JoinHandle, Thread};
 use std::time::{Duration, Instant};
@@ -20,25 +34,69 @@ pub(crate) const IS_ALIVE: i64 = 1;
 /// Define constants for states and requests
 pub(crate) const NOT_ALIVE: i64 = 0;
 
-/// The number of points that can be stored in the shared memory
-const POINT_ARRAY_SIZE: usize = ((1024 - 8) / 2) as usize;
+/// File the daemon keeps the registered templates in, unless it is given another one.
+pub(crate) const DEFAULT_TEMPLATE_FILE: &str = "gesture_templates.txt";
+
+/// How long the client waits for the server to acknowledge a request before giving up on the
+/// service. The server is woken by a futex, so an acknowledgement normally takes microseconds.
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(40);
+
+/// How long the server sleeps on the command ring before checking `client_request` again while
+/// streaming. Commands and HALT wake it straight away; this only bounds how long a request from a
+/// client that only wakes `client_request` can go unnoticed.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The number of `f64` values stored per point: x, y, timestamp, pressure and pointer id, in that
+/// order. A missing timestamp or pressure is stored as NaN.
+const POINT_FIELDS: usize = 5;
+
+/// The number of bytes used by one point in the point array
+const POINT_SIZE: usize = POINT_FIELDS * size_of::<f64>();
+
+/// The number of points that can be stored in the shared memory after the fixed fields
+pub(crate) const POINT_ARRAY_SIZE: usize = (SHARED_MEMORY_SIZE - size_of::<GestureDetectionIPCBuffer>()) / POINT_SIZE;
+
+/// The number of bytes used by one command in the command ring
+const COMMAND_SLOT_SIZE: usize = size_of::<CommandSlot>();
+
+/// The number of commands the ring can hold. The ring shares the space after the fixed fields
+/// with the point array, since a gesture is either streamed or sent in one go.
+pub(crate) const COMMAND_RING_CAPACITY: usize = (SHARED_MEMORY_SIZE - size_of::<GestureDetectionIPCBuffer>()) / COMMAND_SLOT_SIZE;
+
+/// The number of bytes used by one template in the template list
+const TEMPLATE_ENTRY_SIZE: usize = size_of::<TemplateEntry>();
+
+/// The number of templates LIST_TEMPLATES can return. The list also uses the point array's space.
+pub(crate) const TEMPLATE_LIST_CAPACITY: usize = (SHARED_MEMORY_SIZE - size_of::<GestureDetectionIPCBuffer>()) / TEMPLATE_ENTRY_SIZE;
+
+/// The number of bytes used by one entry in the disqualification list
+const DISQUALIFICATION_ENTRY_SIZE: usize = size_of::<DisqualificationEntry>();
+
+/// The number of disqualifications LIST_DISQUALIFICATIONS can return. The list also uses the point
+/// array's space.
+pub(crate) const DISQUALIFICATION_LIST_CAPACITY: usize =
+    (SHARED_MEMORY_SIZE - size_of::<GestureDetectionIPCBuffer>()) / DISQUALIFICATION_ENTRY_SIZE;
 
 
 /// Bytewise offset to load the duration argument
-const DURATION_OFFSET: u32 = 24;
+const DURATION_OFFSET: u32 = 48;
 
 /// Bytewise offset to load the fling direction argument
-const FLING_DIRECTION_OFFSET: u32 = 40;
+const FLING_DIRECTION_OFFSET: u32 = 56;
 
 /// Bytewise offset to load the point array size argument
-const POINT_ARRAY_SIZE_OFFSET: u32 = 48;
+const POINT_ARRAY_SIZE_OFFSET: u32 = 64;
 
 /// Bytewise offset to load the result argument
-const RESULT_OFFSET: u32 = 56;
+const RESULT_OFFSET: u32 = 72;
 
-/// Bytewise offset to load the point array start argument
-/// The last field in the shared memory space is the point array size, which is a 64-bit integer.
-pub const POINT_ARRAY_OFFSET: u8 = 64;
+/// Bytewise offset of the first point.
+/// The point array starts right after the last fixed field, `compass_sector`, which is 8 bytes at 440.
+pub const POINT_ARRAY_OFFSET: u16 = 448;
+
+
+/// Stored in `template_id` when the result is not a template match.
+pub(crate) const NO_TEMPLATE: i64 = -1;
 
 /// Bitwise offset to bit-shift for a click result
 const CLICK_INDEX_BITWISE_OFFSET: u32 = 0;
@@ -57,17 +115,60 @@ const CIRCLE_BITWISE_OFFSET: u32 = 18;
 
 
 /// Bitwise offset to bit-shift for an error result
-const ERROR_BITWISE_OFFSET: u32 = 20;
+pub(crate) const ERROR_BITWISE_OFFSET: u32 = 20;
+
+
+/// Bitwise offset to bit-shift for a pinch result
+const PINCH_BITWISE_OFFSET: u32 = 21;
+
+
+/// Bitwise offset to bit-shift for a spread result
+const SPREAD_BITWISE_OFFSET: u32 = 22;
+
+
+/// Bitwise offset to bit-shift for a two-finger rotation result
+const TWO_FINGER_ROTATION_BITWISE_OFFSET: u32 = 23;
+
+
+/// Bitwise offset to bit-shift for a multi-finger swipe result
+const MULTI_SWIPE_BITWISE_OFFSET: u32 = 25;
+
+
+/// Bitwise offset to bit-shift for a double tap result
+const DOUBLE_TAP_BITWISE_OFFSET: u32 = 33;
+
+
+/// Bitwise offset to bit-shift for a triple (or longer) tap result
+const TRIPLE_TAP_BITWISE_OFFSET: u32 = 34;
+
+
+/// Bitwise offset to bit-shift for a hold-then-drag result
+const HOLD_DRAG_BITWISE_OFFSET: u32 = 35;
+
+
+/// Bitwise offset to bit-shift for a template match result
+const TEMPLATE_BITWISE_OFFSET: u32 = 43;
+
+/// The provisional result word is stored in the low 48 bits of `provisional_result`, and its
+/// confidence, in thousandths, in the bits above this offset.
+const PROVISIONAL_CONFIDENCE_BITWISE_OFFSET: u32 = 48;
+
+/// `provisional_result` stores the confidence as a whole number out of this scale.
+const PROVISIONAL_CONFIDENCE_SCALE: f64 = 1000.0;
 
 
 #[derive(Debug, Clone, Copy)]
-enum ServerState {
+pub(crate) enum ServerState {
     WaitingForCommand,
     WaitingForArgs,
     DetectingGesture,
     PingAcknowledged,
     DetectionComplete,
     Exited,
+    Streaming,
+    TemplateRequestComplete,
+    ConfigurationComplete,
+    DebugRequestComplete,
 }
 
 impl ServerState {
@@ -79,6 +180,10 @@ impl ServerState {
             ServerState::PingAcknowledged => 3,
             ServerState::DetectionComplete => 4,
             ServerState::Exited => 5,
+            ServerState::Streaming => 6,
+            ServerState::TemplateRequestComplete => 7,
+            ServerState::ConfigurationComplete => 8,
+            ServerState::DebugRequestComplete => 9,
         }
     }
     pub(crate) fn from_java_ordinal(ordinal: i64) -> Self {
@@ -89,6 +194,10 @@ impl ServerState {
             3 => ServerState::PingAcknowledged,
             4 => ServerState::DetectionComplete,
             5 => ServerState::Exited,
+            6 => ServerState::Streaming,
+            7 => ServerState::TemplateRequestComplete,
+            8 => ServerState::ConfigurationComplete,
+            9 => ServerState::DebugRequestComplete,
             _ => ServerState::WaitingForCommand,
         }
     }
@@ -100,6 +209,10 @@ impl ServerState {
             "PING_ACKNOWLEDGED" => ServerState::PingAcknowledged,
             "DETECTION_COMPLETE" => ServerState::DetectionComplete,
             "EXITED" => ServerState::Exited,
+            "STREAMING" => ServerState::Streaming,
+            "TEMPLATE_REQUEST_COMPLETE" => ServerState::TemplateRequestComplete,
+            "CONFIGURATION_COMPLETE" => ServerState::ConfigurationComplete,
+            "DEBUG_REQUEST_COMPLETE" => ServerState::DebugRequestComplete,
             _ => ServerState::WaitingForCommand,
         }
     }
@@ -111,18 +224,28 @@ impl ServerState {
             ServerState::PingAcknowledged => "PING_ACKNOWLEDGED",
             ServerState::DetectionComplete => "DETECTION_COMPLETE",
             ServerState::Exited => "EXITED",
+            ServerState::Streaming => "STREAMING",
+            ServerState::TemplateRequestComplete => "TEMPLATE_REQUEST_COMPLETE",
+            ServerState::ConfigurationComplete => "CONFIGURATION_COMPLETE",
+            ServerState::DebugRequestComplete => "DEBUG_REQUEST_COMPLETE",
         })
     }
 }
 
 #[derive(Debug, Clone, Copy)]
-enum ClientRequest {
+pub(crate) enum ClientRequest {
     Uninitialized,
     Ping,
     Begin,
     Detect,
     Halt,
     ClearAcknowledgement,
+    Stream,
+    RegisterTemplate,
+    RemoveTemplate,
+    ListTemplates,
+    Configure,
+    ListDisqualifications,
 }
 
 impl ClientRequest {
@@ -132,8 +255,14 @@ impl ClientRequest {
             ClientRequest::Ping => 1,
             ClientRequest::Begin => 2,
             ClientRequest::Detect => 3,
-            ClientRequest::Halt => 4,
-            ClientRequest::ClearAcknowledgement => 5,
+            ClientRequest::ClearAcknowledgement => 4,
+            ClientRequest::Halt => 5,
+            ClientRequest::Stream => 6,
+            ClientRequest::RegisterTemplate => 7,
+            ClientRequest::RemoveTemplate => 8,
+            ClientRequest::ListTemplates => 9,
+            ClientRequest::Configure => 10,
+            ClientRequest::ListDisqualifications => 11,
         }
     }
     pub fn java_value_of(ordinal: i64) -> Self {
@@ -144,6 +273,12 @@ impl ClientRequest {
             3 => ClientRequest::Detect,
             4 => ClientRequest::ClearAcknowledgement,
             5 => ClientRequest::Halt,
+            6 => ClientRequest::Stream,
+            7 => ClientRequest::RegisterTemplate,
+            8 => ClientRequest::RemoveTemplate,
+            9 => ClientRequest::ListTemplates,
+            10 => ClientRequest::Configure,
+            11 => ClientRequest::ListDisqualifications,
             _ => ClientRequest::Uninitialized,
         }
     }
@@ -155,17 +290,29 @@ impl ClientRequest {
             "DETECT" => ClientRequest::Detect,
             "CLEAR_ACKNOWLEDGEMENT" => ClientRequest::ClearAcknowledgement,
             "HALT" => ClientRequest::Halt,
+            "STREAM" => ClientRequest::Stream,
+            "REGISTER_TEMPLATE" => ClientRequest::RegisterTemplate,
+            "REMOVE_TEMPLATE" => ClientRequest::RemoveTemplate,
+            "LIST_TEMPLATES" => ClientRequest::ListTemplates,
+            "CONFIGURE" => ClientRequest::Configure,
+            "LIST_DISQUALIFICATIONS" => ClientRequest::ListDisqualifications,
             _ => ClientRequest::Uninitialized,
         }
     }
     pub fn name(&self) -> String {
         String::from(match self {
-            Empty => "EMPTY",
+            Uninitialized => "EMPTY",
             Ping => "PING",
             Begin => "BEGIN",
             Detect => "DETECT",
             ClearAcknowledgement => "CLEAR_ACKNOWLEDGEMENT",
             Halt => "HALT",
+            Stream => "STREAM",
+            RegisterTemplate => "REGISTER_TEMPLATE",
+            RemoveTemplate => "REMOVE_TEMPLATE",
+            ListTemplates => "LIST_TEMPLATES",
+            Configure => "CONFIGURE",
+            ListDisqualifications => "LIST_DISQUALIFICATIONS",
         })
     }
     pub fn from_java_ordinal(ordinal: i64) -> Self {
@@ -176,6 +323,12 @@ impl ClientRequest {
             3 => Detect,
             4 => ClearAcknowledgement,
             5 => Halt,
+            6 => Stream,
+            7 => RegisterTemplate,
+            8 => RemoveTemplate,
+            9 => ListTemplates,
+            10 => Configure,
+            11 => ListDisqualifications,
             _ => Uninitialized,
         }
     }
@@ -183,67 +336,603 @@ impl ClientRequest {
 
 /// This enum represents the offsets for each field in the shared memory space.
 enum SharedMemoryOffset {
-    IsAlive = 0,
-    ServiceState = 8,
-    ClientRequest = 16,
-    Duration = 24,
-    FlingDirection = 40,
-    PointArraySize = 48,
-    Result = 56,
-    PointArrayStart = 64,
+    Magic = 0,
+    ProtocolVersion = 4,
+    HeaderLength = 8,
+    SegmentLength = 12,
+    PointCapacity = 16,
+    IsAlive = 24,
+    ServiceState = 32,
+    ClientRequest = 40,
+    Duration = 48,
+    FlingDirection = 56,
+    PointArraySize = 64,
+    Result = 72,
+    PointArrayStart = 80,
+    CommandHead = 88,
+    CommandTail = 96,
+    ProvisionalResult = 104,
+    ResultParameter = 112,
+    TemplateId = 120,
+    TemplateName = 128,
+    DirectionAngle = 144,
+    DirectionDeadZone = 152,
+    DirectionBoundaryPolicy = 160,
+    Detector = 168,
+    HoldDuration = 176,
+    CircleRadius = 184,
+    DragLength = 192,
+    DisplayDensity = 200,
+    DisplayWidth = 208,
+    DisplayHeight = 216,
+    TapInterval = 224,
+    TapDistance = 232,
+    CompassMode = 240,
+    ResultRecord = 248,
+    Candidates = 376,
+    CompassSector = 440,
+    Points = 448,
 }
 
 
 /// This struct represents the memory space used for the entire communication layer between this
 /// detection service and the client.
+///
+/// `is_alive`, `server_state` and `client_request` form the handshake. Each of them has exactly one
+/// writer (the server writes `is_alive` and `server_state`, the client writes `client_request`), and
+/// they are stored with release and loaded with acquire ordering. The remaining fields are payload:
+/// the client only writes them before publishing a request, and the server only writes them before
+/// publishing a new state, so whoever holds the turn has exclusive access to them.
+///
+/// `command_head` and `command_tail` index the command ring used while streaming. The client
+/// writes a command into its slot before advancing `command_head`, and the server reads it before
+/// advancing `command_tail`, so the ring needs no turn taking.
 #[repr(C)]
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub(crate) struct GestureDetectionIPCBuffer {
-    pub(crate) is_alive: i64, // offset=0, +8
-    pub(crate) server_state: i64, // offset=8, +8
-    client_request: i64, // offset=16, +8
-    duration: i128, // offset=24, +16
+    pub(crate) header: GestureDetectionIPCHeader, // offset=0, +24
+    pub(crate) is_alive: AtomicI64, // offset=24, +8
+    pub(crate) server_state: AtomicI64, // offset=32, +8
+    pub(crate) client_request: AtomicI64, // offset=40, +8
+    /// Milliseconds, as a Java long. An i128 would need 16-byte alignment and leave a hole.
+    duration: UnsafeCell<i64>, // offset=48, +8
 
     /// The implied direction of the gesture at its last known position.
-    fling_direction: i64, // offset=40, +8
-    point_array_size: i64, // offset=48, +8
-    result: i64, // offset=56, +8
-    point_array_start: i64, // offset=64, +8
+    fling_direction: UnsafeCell<i64>, // offset=56, +8
+    point_array_size: UnsafeCell<i64>, // offset=64, +8
+    result: UnsafeCell<i64>, // offset=72, +8
+    point_array_start: UnsafeCell<i64>, // offset=80, +8
+    /// Number of commands the client has ever written to the command ring.
+    pub(crate) command_head: AtomicI64, // offset=88, +8
+    /// Number of commands the server has ever read from the command ring.
+    pub(crate) command_tail: AtomicI64, // offset=96, +8
+    /// The result the streamed gesture would have if it ended now, with its confidence. Zero
+    /// until the first point of a stream has been classified. Only the server writes it.
+    pub(crate) provisional_result: AtomicI64, // offset=104, +8
+    /// The measurement that goes with a multi-touch, multi-tap or template `result`: the scale
+    /// factor of a pinch or spread, the angle of a rotation in degrees, the number of fingers of a
+    /// swipe, the number of taps, or the score of a template match. Zero for every other result.
+    result_parameter: UnsafeCell<f64>, // offset=112, +8
+    /// The id of the template that matched, or `NO_TEMPLATE`. The client writes the id of the
+    /// template to remove here for REMOVE_TEMPLATE.
+    template_id: UnsafeCell<i64>, // offset=120, +8
+    /// Name of the template to register for REGISTER_TEMPLATE, in UTF-8 padded with zeros.
+    template_name: UnsafeCell<[u8; TEMPLATE_NAME_LENGTH]>, // offset=128, +16
+    /// Heading of a directional `result` in degrees, anticlockwise from East with North up the
+    /// screen, for clients that need more than its 8 directions. NaN for every other result.
+    direction_angle: UnsafeCell<f64>, // offset=144, +8
+    /// Settings for CONFIGURE, written by the client. The server writes back the settings in
+    /// effect, whether it accepted them or not.
+    configuration: UnsafeCell<ConfigurationBlock>, // offset=152, +96
+    /// The outcome of the last detection as a structured record, see `ResultRecord`.
+    result_record: UnsafeCell<ResultRecord>, // offset=248, +128
+    /// The gesture types the last detection could have been, best first, see `CandidateEntry`.
+    candidates: UnsafeCell<[CandidateEntry; CANDIDATE_COUNT]>, // offset=376, +64
+    /// Ordinal of the sector `direction_angle` falls in, on the compass the configured
+    /// `CompassMode` selects. -1 whenever `direction_angle` is NaN.
+    compass_sector: UnsafeCell<i64>, // offset=440, +8
 }
 
+// The offsets above are what clients in other languages are built against, so make sure they
+// match what the compiler actually does with the #[repr(C)] structs.
+const _: () = {
+    assert!(size_of::<GestureDetectionIPCHeader>() == SharedMemoryOffset::IsAlive as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, header) == SharedMemoryOffset::Magic as usize);
+    assert!(offset_of!(GestureDetectionIPCHeader, magic) == SharedMemoryOffset::Magic as usize);
+    assert!(offset_of!(GestureDetectionIPCHeader, protocol_version) == SharedMemoryOffset::ProtocolVersion as usize);
+    assert!(offset_of!(GestureDetectionIPCHeader, header_length) == SharedMemoryOffset::HeaderLength as usize);
+    assert!(offset_of!(GestureDetectionIPCHeader, segment_length) == SharedMemoryOffset::SegmentLength as usize);
+    assert!(offset_of!(GestureDetectionIPCHeader, point_capacity) == SharedMemoryOffset::PointCapacity as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, is_alive) == SharedMemoryOffset::IsAlive as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, server_state) == SharedMemoryOffset::ServiceState as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, client_request) == SharedMemoryOffset::ClientRequest as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, duration) == SharedMemoryOffset::Duration as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, duration) == DURATION_OFFSET as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, fling_direction) == SharedMemoryOffset::FlingDirection as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, fling_direction) == FLING_DIRECTION_OFFSET as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, point_array_size) == SharedMemoryOffset::PointArraySize as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, point_array_size) == POINT_ARRAY_SIZE_OFFSET as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, result) == SharedMemoryOffset::Result as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, result) == RESULT_OFFSET as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, point_array_start) == SharedMemoryOffset::PointArrayStart as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, command_head) == SharedMemoryOffset::CommandHead as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, command_tail) == SharedMemoryOffset::CommandTail as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, provisional_result) == SharedMemoryOffset::ProvisionalResult as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, result_parameter) == SharedMemoryOffset::ResultParameter as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, template_id) == SharedMemoryOffset::TemplateId as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, template_name) == SharedMemoryOffset::TemplateName as usize);
+    assert!(offset_of!(GestureDetectionIPCBuffer, direction_angle) == SharedMemoryOffset::DirectionAngle as usize);
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, direction_dead_zone)
+            == SharedMemoryOffset::DirectionDeadZone as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, direction_boundary_policy)
+            == SharedMemoryOffset::DirectionBoundaryPolicy as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, detector)
+            == SharedMemoryOffset::Detector as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, hold_duration)
+            == SharedMemoryOffset::HoldDuration as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, circle_radius)
+            == SharedMemoryOffset::CircleRadius as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, drag_length)
+            == SharedMemoryOffset::DragLength as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, display_density)
+            == SharedMemoryOffset::DisplayDensity as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, display_width)
+            == SharedMemoryOffset::DisplayWidth as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, display_height)
+            == SharedMemoryOffset::DisplayHeight as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, tap_interval)
+            == SharedMemoryOffset::TapInterval as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, tap_distance)
+            == SharedMemoryOffset::TapDistance as usize
+    );
+    assert!(
+        offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, compass_mode)
+            == SharedMemoryOffset::CompassMode as usize
+    );
+    assert!(offset_of!(GestureDetectionIPCBuffer, result_record) == SharedMemoryOffset::ResultRecord as usize);
+    assert!(size_of::<ResultRecord>() == 16 * size_of::<i64>());
+    assert!(offset_of!(GestureDetectionIPCBuffer, candidates) == SharedMemoryOffset::Candidates as usize);
+    assert!(size_of::<CandidateEntry>() == 2 * size_of::<i64>());
+    assert!(offset_of!(GestureDetectionIPCBuffer, compass_sector) == SharedMemoryOffset::CompassSector as usize);
+    assert!(TEMPLATE_ENTRY_SIZE == size_of::<i64>() + TEMPLATE_NAME_LENGTH);
+    assert!(POINT_ARRAY_OFFSET as usize + TEMPLATE_LIST_CAPACITY * TEMPLATE_ENTRY_SIZE <= SHARED_MEMORY_SIZE);
+    assert!(DISQUALIFICATION_ENTRY_SIZE == 4 * size_of::<i64>());
+    assert!(POINT_ARRAY_OFFSET as usize + DISQUALIFICATION_LIST_CAPACITY * DISQUALIFICATION_ENTRY_SIZE <= SHARED_MEMORY_SIZE);
+    assert!(COMMAND_SLOT_SIZE == size_of::<i64>() + POINT_SIZE);
+    assert!(size_of::<GestureDetectionIPCBuffer>() == SharedMemoryOffset::Points as usize);
+    assert!(size_of::<GestureDetectionIPCBuffer>() == POINT_ARRAY_OFFSET as usize);
+    assert!(POINT_ARRAY_OFFSET as usize + POINT_ARRAY_SIZE * POINT_SIZE <= SHARED_MEMORY_SIZE);
+    assert!(POINT_ARRAY_OFFSET as usize + COMMAND_RING_CAPACITY * COMMAND_SLOT_SIZE <= SHARED_MEMORY_SIZE);
+};
+
 impl GestureDetectionIPCBuffer {
+    /// Description of the segment for clients written in other languages, see `ipc_layout.rs`.
+    pub(crate) fn layout() -> Layout {
+        fn field<T>(name: &'static str, offset: usize, rust_type: &'static str) -> LayoutField {
+            LayoutField { name, offset, size: size_of::<T>(), rust_type }
+        }
+        let result_record = offset_of!(GestureDetectionIPCBuffer, result_record);
+        let candidates = offset_of!(GestureDetectionIPCBuffer, candidates);
+
+        let mut constants = vec![
+            ("IS_ALIVE", IS_ALIVE),
+            ("NOT_ALIVE", NOT_ALIVE),
+            ("CLICK_INDEX_BITWISE_OFFSET", CLICK_INDEX_BITWISE_OFFSET as i64),
+            ("BOOMERANG_BITWISE_OFFSET", BOOMERANG_BITWISE_OFFSET as i64),
+            ("SWIPE_BITWISE_OFFSET", SWIPE_BITWISE_OFFSET as i64),
+            ("CIRCLE_BITWISE_OFFSET", CIRCLE_BITWISE_OFFSET as i64),
+            ("ERROR_BITWISE_OFFSET", ERROR_BITWISE_OFFSET as i64),
+            ("PINCH_BITWISE_OFFSET", PINCH_BITWISE_OFFSET as i64),
+            ("SPREAD_BITWISE_OFFSET", SPREAD_BITWISE_OFFSET as i64),
+            ("TWO_FINGER_ROTATION_BITWISE_OFFSET", TWO_FINGER_ROTATION_BITWISE_OFFSET as i64),
+            ("MULTI_SWIPE_BITWISE_OFFSET", MULTI_SWIPE_BITWISE_OFFSET as i64),
+            ("DOUBLE_TAP_BITWISE_OFFSET", DOUBLE_TAP_BITWISE_OFFSET as i64),
+            ("TRIPLE_TAP_BITWISE_OFFSET", TRIPLE_TAP_BITWISE_OFFSET as i64),
+            ("HOLD_DRAG_BITWISE_OFFSET", HOLD_DRAG_BITWISE_OFFSET as i64),
+            ("TEMPLATE_BITWISE_OFFSET", TEMPLATE_BITWISE_OFFSET as i64),
+            ("NO_TEMPLATE", NO_TEMPLATE),
+            ("TEMPLATE_NAME_LENGTH", TEMPLATE_NAME_LENGTH as i64),
+            ("TEMPLATE_ENTRY_SIZE", TEMPLATE_ENTRY_SIZE as i64),
+            ("TEMPLATE_LIST_CAPACITY", TEMPLATE_LIST_CAPACITY as i64),
+            ("DISQUALIFICATION_ENTRY_SIZE", DISQUALIFICATION_ENTRY_SIZE as i64),
+            ("DISQUALIFICATION_LIST_CAPACITY", DISQUALIFICATION_LIST_CAPACITY as i64),
+            ("DISQUALIFICATION_RULE_OFFSET", offset_of!(DisqualificationEntry, rule) as i64),
+            ("DISQUALIFICATION_GESTURES_OFFSET", offset_of!(DisqualificationEntry, gestures) as i64),
+            ("DISQUALIFICATION_MEASURED_OFFSET", offset_of!(DisqualificationEntry, measured) as i64),
+            ("DISQUALIFICATION_THRESHOLD_OFFSET", offset_of!(DisqualificationEntry, threshold) as i64),
+            ("PROVISIONAL_CONFIDENCE_BITWISE_OFFSET", PROVISIONAL_CONFIDENCE_BITWISE_OFFSET as i64),
+            ("PROVISIONAL_CONFIDENCE_SCALE", PROVISIONAL_CONFIDENCE_SCALE as i64),
+            ("POINT_X_OFFSET", 0),
+            ("POINT_Y_OFFSET", size_of::<f64>() as i64),
+            ("POINT_TIMESTAMP_OFFSET", 2 * size_of::<f64>() as i64),
+            ("POINT_PRESSURE_OFFSET", 3 * size_of::<f64>() as i64),
+            ("POINT_POINTER_ID_OFFSET", 4 * size_of::<f64>() as i64),
+            ("COMMAND_SLOT_SIZE", COMMAND_SLOT_SIZE as i64),
+            ("COMMAND_RING_CAPACITY", COMMAND_RING_CAPACITY as i64),
+        ];
+        constants.extend(GenericGestureType::values().into_iter().map(|gesture| match gesture {
+            GenericGestureType::Click => ("GESTURE_TYPE_CLICK", gesture.ordinal()),
+            GenericGestureType::Hold => ("GESTURE_TYPE_HOLD", gesture.ordinal()),
+            GenericGestureType::Swipe => ("GESTURE_TYPE_SWIPE", gesture.ordinal()),
+            GenericGestureType::Circle => ("GESTURE_TYPE_CIRCLE", gesture.ordinal()),
+            GenericGestureType::Boomerang => ("GESTURE_TYPE_BOOMERANG", gesture.ordinal()),
+            GenericGestureType::Pinch => ("GESTURE_TYPE_PINCH", gesture.ordinal()),
+            GenericGestureType::Spread => ("GESTURE_TYPE_SPREAD", gesture.ordinal()),
+            GenericGestureType::Rotate => ("GESTURE_TYPE_ROTATE", gesture.ordinal()),
+            GenericGestureType::MultiSwipe => ("GESTURE_TYPE_MULTI_SWIPE", gesture.ordinal()),
+            GenericGestureType::HoldDrag => ("GESTURE_TYPE_HOLD_DRAG", gesture.ordinal()),
+        }));
+        constants.extend([
+            ("NO_ORDINAL", NO_ORDINAL),
+            ("CANDIDATE_COUNT", CANDIDATE_COUNT as i64),
+            ("CANDIDATE_ENTRY_SIZE", size_of::<CandidateEntry>() as i64),
+            ("CANDIDATE_GESTURE_TYPE_OFFSET", offset_of!(CandidateEntry, gesture_type) as i64),
+            ("CANDIDATE_SCORE_OFFSET", offset_of!(CandidateEntry, score) as i64),
+            ("ERROR_CODE_INVALID_POINT_ARRAY", DetectionError::InvalidPointArray.java_ordinal()),
+            ("ERROR_CODE_MISSING_POINTS", DetectionError::MissingPoints.java_ordinal()),
+        ]);
+        constants.extend(Rule::values().into_iter().map(|rule| match rule {
+            Rule::PointerCount => ("RULE_POINTER_COUNT", rule.java_ordinal()),
+            Rule::DragLength => ("RULE_DRAG_LENGTH", rule.java_ordinal()),
+            Rule::HoldDuration => ("RULE_HOLD_DURATION", rule.java_ordinal()),
+            Rule::HoldBeforeDrag => ("RULE_HOLD_BEFORE_DRAG", rule.java_ordinal()),
+            Rule::CircleRadius => ("RULE_CIRCLE_RADIUS", rule.java_ordinal()),
+            Rule::Reversal => ("RULE_REVERSAL", rule.java_ordinal()),
+            Rule::BoomerangReturn => ("RULE_BOOMERANG_RETURN", rule.java_ordinal()),
+            Rule::CircleTurning => ("RULE_CIRCLE_TURNING", rule.java_ordinal()),
+            Rule::SwipeTurning => ("RULE_SWIPE_TURNING", rule.java_ordinal()),
+            Rule::MultiSwipeDistance => ("RULE_MULTI_SWIPE_DISTANCE", rule.java_ordinal()),
+            Rule::RotationAngle => ("RULE_ROTATION_ANGLE", rule.java_ordinal()),
+            Rule::ScaleFactor => ("RULE_SCALE_FACTOR", rule.java_ordinal()),
+            Rule::MultiTouchMovement => ("RULE_MULTI_TOUCH_MOVEMENT", rule.java_ordinal()),
+        }));
+        constants.extend([
+            ("BOUNDARY_POLICY_NEAREST", BoundaryPolicy::Nearest),
+            ("BOUNDARY_POLICY_AMBIGUOUS", BoundaryPolicy::Ambiguous),
+            ("BOUNDARY_POLICY_CARDINAL", BoundaryPolicy::Cardinal),
+        ].map(|(name, policy)| (name, policy.java_ordinal())));
+        constants.extend([
+            ("COMPASS_MODE_EIGHT_POINT", CompassMode::EightPoint),
+            ("COMPASS_MODE_SIXTEEN_POINT", CompassMode::SixteenPoint),
+        ].map(|(name, compass_mode)| (name, compass_mode.java_ordinal())));
+        constants.extend([
+            ("DETECTOR_RULE_BASED", RULE_BASED_DETECTOR),
+            ("DETECTOR_TEMPLATE_BASED", TEMPLATE_BASED_DETECTOR),
+            ("DETECTOR_DUMMY", DUMMY_DETECTOR),
+        ].map(|(name, detector)| (name, detector as i64)));
+        constants.extend([
+            ("SERVER_STATE_WAITING_FOR_COMMAND", WaitingForCommand),
+            ("SERVER_STATE_WAITING_FOR_ARGS", WaitingForArgs),
+            ("SERVER_STATE_DETECTING_GESTURE", DetectingGesture),
+            ("SERVER_STATE_PING_ACKNOWLEDGED", PingAcknowledged),
+            ("SERVER_STATE_DETECTION_COMPLETE", DetectionComplete),
+            ("SERVER_STATE_EXITED", Exited),
+            ("SERVER_STATE_STREAMING", Streaming),
+            ("SERVER_STATE_TEMPLATE_REQUEST_COMPLETE", TemplateRequestComplete),
+            ("SERVER_STATE_CONFIGURATION_COMPLETE", ConfigurationComplete),
+            ("SERVER_STATE_DEBUG_REQUEST_COMPLETE", DebugRequestComplete),
+        ].map(|(name, state)| (name, state.java_ordinal())));
+        constants.extend([
+            ("CLIENT_REQUEST_EMPTY", Uninitialized),
+            ("CLIENT_REQUEST_PING", Ping),
+            ("CLIENT_REQUEST_BEGIN", Begin),
+            ("CLIENT_REQUEST_DETECT", Detect),
+            ("CLIENT_REQUEST_CLEAR_ACKNOWLEDGEMENT", ClearAcknowledgement),
+            ("CLIENT_REQUEST_HALT", Halt),
+            ("CLIENT_REQUEST_STREAM", Stream),
+            ("CLIENT_REQUEST_REGISTER_TEMPLATE", RegisterTemplate),
+            ("CLIENT_REQUEST_REMOVE_TEMPLATE", RemoveTemplate),
+            ("CLIENT_REQUEST_LIST_TEMPLATES", ListTemplates),
+            ("CLIENT_REQUEST_CONFIGURE", Configure),
+            ("CLIENT_REQUEST_LIST_DISQUALIFICATIONS", ListDisqualifications),
+        ].map(|(name, request)| (name, request.java_ordinal())));
+        constants.extend([
+            ("CLIENT_COMMAND_COLLECT", ClientCommand::Collect { point: Point::new(0.0, 0.0) }),
+            ("CLIENT_COMMAND_INSERT", ClientCommand::Insert { point: Point::new(0.0, 0.0) }),
+            ("CLIENT_COMMAND_DETECT", ClientCommand::Detect { duration: 0, count: 0, fling_direction: None }),
+            ("CLIENT_COMMAND_RESET", ClientCommand::Reset),
+        ].map(|(name, command)| (name, command.java_ordinal())));
+
+        Layout {
+            protocol_version: PROTOCOL_VERSION,
+            magic: MAGIC,
+            segment_length: SHARED_MEMORY_SIZE,
+            header_length: size_of::<GestureDetectionIPCBuffer>(),
+            point_size: POINT_SIZE,
+            point_capacity: POINT_ARRAY_SIZE,
+            fields: vec![
+                field::<u32>("magic", offset_of!(GestureDetectionIPCHeader, magic), "u32"),
+                field::<u32>("protocol_version", offset_of!(GestureDetectionIPCHeader, protocol_version), "u32"),
+                field::<u32>("header_length", offset_of!(GestureDetectionIPCHeader, header_length), "u32"),
+                field::<u32>("segment_length", offset_of!(GestureDetectionIPCHeader, segment_length), "u32"),
+                field::<u32>("point_capacity", offset_of!(GestureDetectionIPCHeader, point_capacity), "u32"),
+                field::<i64>("is_alive", offset_of!(GestureDetectionIPCBuffer, is_alive), "i64"),
+                field::<i64>("server_state", offset_of!(GestureDetectionIPCBuffer, server_state), "i64"),
+                field::<i64>("client_request", offset_of!(GestureDetectionIPCBuffer, client_request), "i64"),
+                field::<i64>("duration", offset_of!(GestureDetectionIPCBuffer, duration), "i64"),
+                field::<i64>("fling_direction", offset_of!(GestureDetectionIPCBuffer, fling_direction), "i64"),
+                field::<i64>("point_array_size", offset_of!(GestureDetectionIPCBuffer, point_array_size), "i64"),
+                field::<i64>("result", offset_of!(GestureDetectionIPCBuffer, result), "i64"),
+                field::<i64>("point_array_start", offset_of!(GestureDetectionIPCBuffer, point_array_start), "i64"),
+                field::<i64>("command_head", offset_of!(GestureDetectionIPCBuffer, command_head), "i64"),
+                field::<i64>("command_tail", offset_of!(GestureDetectionIPCBuffer, command_tail), "i64"),
+                field::<i64>("provisional_result", offset_of!(GestureDetectionIPCBuffer, provisional_result), "i64"),
+                field::<f64>("result_parameter", offset_of!(GestureDetectionIPCBuffer, result_parameter), "f64"),
+                field::<i64>("template_id", offset_of!(GestureDetectionIPCBuffer, template_id), "i64"),
+                field::<[u8; TEMPLATE_NAME_LENGTH]>("template_name", offset_of!(GestureDetectionIPCBuffer, template_name), "[u8; 16]"),
+                field::<f64>("direction_angle", offset_of!(GestureDetectionIPCBuffer, direction_angle), "f64"),
+                field::<f64>(
+                    "direction_dead_zone",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, direction_dead_zone),
+                    "f64",
+                ),
+                field::<i64>(
+                    "direction_boundary_policy",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, direction_boundary_policy),
+                    "i64",
+                ),
+                field::<i64>("detector", offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, detector), "i64"),
+                field::<i64>(
+                    "hold_duration",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, hold_duration),
+                    "i64",
+                ),
+                field::<f64>(
+                    "circle_radius",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, circle_radius),
+                    "f64",
+                ),
+                field::<f64>(
+                    "drag_length",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, drag_length),
+                    "f64",
+                ),
+                field::<f64>(
+                    "display_density",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, display_density),
+                    "f64",
+                ),
+                field::<f64>(
+                    "display_width",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, display_width),
+                    "f64",
+                ),
+                field::<f64>(
+                    "display_height",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, display_height),
+                    "f64",
+                ),
+                field::<i64>(
+                    "tap_interval",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, tap_interval),
+                    "i64",
+                ),
+                field::<f64>(
+                    "tap_distance",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, tap_distance),
+                    "f64",
+                ),
+                field::<i64>(
+                    "compass_mode",
+                    offset_of!(GestureDetectionIPCBuffer, configuration) + offset_of!(ConfigurationBlock, compass_mode),
+                    "i64",
+                ),
+                field::<i64>("result_gesture_type", result_record + offset_of!(ResultRecord, gesture_type), "i64"),
+                field::<i64>("result_direction", result_record + offset_of!(ResultRecord, direction), "i64"),
+                field::<i64>("result_alternative_direction", result_record + offset_of!(ResultRecord, alternative_direction), "i64"),
+                field::<i64>("result_rotation", result_record + offset_of!(ResultRecord, rotation), "i64"),
+                field::<f64>("result_measurement", result_record + offset_of!(ResultRecord, measurement), "f64"),
+                field::<i64>("result_taps", result_record + offset_of!(ResultRecord, taps), "i64"),
+                field::<f64>("result_confidence", result_record + offset_of!(ResultRecord, confidence), "f64"),
+                field::<i64>("result_error_code", result_record + offset_of!(ResultRecord, error_code), "i64"),
+                field::<f64>("result_min_x", result_record + offset_of!(ResultRecord, min_x), "f64"),
+                field::<f64>("result_min_y", result_record + offset_of!(ResultRecord, min_y), "f64"),
+                field::<f64>("result_max_x", result_record + offset_of!(ResultRecord, max_x), "f64"),
+                field::<f64>("result_max_y", result_record + offset_of!(ResultRecord, max_y), "f64"),
+                field::<f64>("result_centroid_x", result_record + offset_of!(ResultRecord, centroid_x), "f64"),
+                field::<f64>("result_centroid_y", result_record + offset_of!(ResultRecord, centroid_y), "f64"),
+                field::<f64>("result_path_length", result_record + offset_of!(ResultRecord, path_length), "f64"),
+                field::<i64>("result_template_id", result_record + offset_of!(ResultRecord, template_id), "i64"),
+                LayoutField { name: "candidates", offset: candidates, size: CANDIDATE_COUNT * size_of::<CandidateEntry>(), rust_type: "[CandidateEntry]" },
+                field::<i64>("compass_sector", offset_of!(GestureDetectionIPCBuffer, compass_sector), "i64"),
+                LayoutField { name: "points", offset: POINT_ARRAY_OFFSET as usize, size: POINT_ARRAY_SIZE * POINT_SIZE, rust_type: "[f64]" },
+            ],
+            constants,
+        }
+    }
+
     pub fn new() -> Self {
         GestureDetectionIPCBuffer {
-            is_alive: IS_ALIVE,
-            server_state: WaitingForCommand.java_ordinal(),
-            client_request: Uninitialized.java_ordinal(),
-            duration: 0,
-            fling_direction: -1,
-            point_array_size: 0,
-            result: 0,
-            point_array_start: POINT_ARRAY_OFFSET as i64,
+            header: GestureDetectionIPCHeader::new(
+                size_of::<GestureDetectionIPCBuffer>() as u32,
+                SHARED_MEMORY_SIZE as u32,
+                POINT_ARRAY_SIZE as u32,
+            ),
+            is_alive: AtomicI64::new(IS_ALIVE),
+            server_state: AtomicI64::new(WaitingForCommand.java_ordinal()),
+            client_request: AtomicI64::new(Uninitialized.java_ordinal()),
+            duration: UnsafeCell::new(0),
+            fling_direction: UnsafeCell::new(-1),
+            point_array_size: UnsafeCell::new(0),
+            result: UnsafeCell::new(0),
+            point_array_start: UnsafeCell::new(POINT_ARRAY_OFFSET as i64),
+            command_head: AtomicI64::new(0),
+            command_tail: AtomicI64::new(0),
+            provisional_result: AtomicI64::new(0),
+            result_parameter: UnsafeCell::new(0.0),
+            template_id: UnsafeCell::new(NO_TEMPLATE),
+            template_name: UnsafeCell::new([0; TEMPLATE_NAME_LENGTH]),
+            direction_angle: UnsafeCell::new(f64::NAN),
+            configuration: UnsafeCell::new(Configuration::default().to_block()),
+            result_record: UnsafeCell::new(ResultRecord::default()),
+            candidates: UnsafeCell::new([CandidateEntry::EMPTY; CANDIDATE_COUNT]),
+            compass_sector: UnsafeCell::new(-1),
         }
     }
-    /// When a client has provided the starting address of the shared memory space, this function
-    /// will create a new instance by casting the address to a pointer to this struct.
-    pub unsafe fn from_raw(address: *mut c_void) -> Self {
-        (&mut *(address as *mut GestureDetectionIPCBuffer)).clone()
+
+    /// Used by whichever side attaches to a segment created by the other one, before touching
+    /// any other field.
+    pub(crate) fn validate_header(&self, mapped_length: usize) -> Result<(), HeaderError> {
+        self.header.validate(size_of::<GestureDetectionIPCBuffer>() as u32, mapped_length, POINT_SIZE as u32)
+    }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        self.is_alive.load(Ordering::Acquire) == IS_ALIVE
+    }
+
+    fn server_state(&self) -> ServerState {
+        ServerState::from_java_ordinal(self.server_state.load(Ordering::Acquire))
+    }
+
+    fn client_request(&self) -> ClientRequest {
+        ClientRequest::from_java_ordinal(self.client_request.load(Ordering::Acquire))
+    }
+
+    fn duration(&self) -> i64 {
+        unsafe { *self.duration.get() }
+    }
+
+    fn set_duration(&self, duration: i64) {
+        unsafe { *self.duration.get() = duration }
+    }
+
+    fn fling_direction(&self) -> i64 {
+        unsafe { *self.fling_direction.get() }
+    }
+
+    fn set_fling_direction(&self, fling_direction: i64) {
+        unsafe { *self.fling_direction.get() = fling_direction }
+    }
+
+    fn point_array_size(&self) -> i64 {
+        unsafe { *self.point_array_size.get() }
+    }
+
+    fn set_point_array_size(&self, point_array_size: i64) {
+        unsafe { *self.point_array_size.get() = point_array_size }
+    }
+
+    fn result(&self) -> i64 {
+        unsafe { *self.result.get() }
+    }
+
+    fn set_result(&self, result: i64) {
+        unsafe { *self.result.get() = result }
+    }
+
+    fn result_parameter(&self) -> f64 {
+        unsafe { *self.result_parameter.get() }
+    }
+
+    fn set_result_parameter(&self, result_parameter: f64) {
+        unsafe { *self.result_parameter.get() = result_parameter }
+    }
+
+    fn template_id(&self) -> i64 {
+        unsafe { *self.template_id.get() }
+    }
+
+    fn set_template_id(&self, template_id: i64) {
+        unsafe { *self.template_id.get() = template_id }
+    }
+
+    fn template_name(&self) -> [u8; TEMPLATE_NAME_LENGTH] {
+        unsafe { *self.template_name.get() }
+    }
+
+    fn set_template_name(&self, template_name: [u8; TEMPLATE_NAME_LENGTH]) {
+        unsafe { *self.template_name.get() = template_name }
+    }
+
+    fn direction_angle(&self) -> f64 {
+        unsafe { *self.direction_angle.get() }
+    }
+
+    fn set_direction_angle(&self, direction_angle: f64) {
+        unsafe { *self.direction_angle.get() = direction_angle }
+    }
+
+    fn configuration(&self) -> ConfigurationBlock {
+        unsafe { *self.configuration.get() }
+    }
+
+    fn set_configuration(&self, configuration: ConfigurationBlock) {
+        unsafe { *self.configuration.get() = configuration }
+    }
+
+    fn result_record(&self) -> ResultRecord {
+        unsafe { *self.result_record.get() }
+    }
+
+    fn set_result_record(&self, result_record: ResultRecord) {
+        unsafe { *self.result_record.get() = result_record }
+    }
+
+    fn candidates(&self) -> [CandidateEntry; CANDIDATE_COUNT] {
+        unsafe { *self.candidates.get() }
+    }
+
+    fn set_candidates(&self, candidates: [CandidateEntry; CANDIDATE_COUNT]) {
+        unsafe { *self.candidates.get() = candidates }
+    }
+
+    fn compass_sector(&self) -> i64 {
+        unsafe { *self.compass_sector.get() }
+    }
+
+    fn set_compass_sector(&self, compass_sector: i64) {
+        unsafe { *self.compass_sector.get() = compass_sector }
+    }
+
+    fn point_array_start(&self) -> i64 {
+        unsafe { *self.point_array_start.get() }
+    }
+
+    fn set_point_array_start(&self, point_array_start: i64) {
+        unsafe { *self.point_array_start.get() = point_array_start }
     }
 }
 
 
-/// Simple wrapper to put the memory struct into
+/// Simple wrapper to put the mapped shared memory segment into, along with the doorbell used to
+/// wake the other side after writing to it.
 #[derive(Clone, Debug)]
-struct GestureDetectionIPC {
-    memory: Arc<Mutex<GestureDetectionIPCBuffer>>,
+pub(crate) struct GestureDetectionIPC {
+    memory: Arc<SharedMemorySegment>,
+    doorbell: Arc<Doorbell>,
 }
 impl GestureDetectionIPC {
-    pub fn new(memory: Arc<Mutex<GestureDetectionIPCBuffer>>) -> Self {
-        GestureDetectionIPC { memory }
+    pub fn new(segment: SharedMemorySegment) -> Self {
+        Self::with_doorbell(segment, Doorbell::Futex)
+    }
+
+    pub fn with_doorbell(segment: SharedMemorySegment, doorbell: Doorbell) -> Self {
+        GestureDetectionIPC { memory: Arc::new(segment), doorbell: Arc::new(doorbell) }
     }
 
     /// Internal helper method to compute the bitwise offset for a result that requires a Direction.
-    pub(crate) fn int_pow2(exponent: u32) -> u32 {
+    pub(crate) fn int_pow2(exponent: u32) -> u64 {
         let mut result = 1;
         for _ in 0..exponent {
             result *= 2;
@@ -253,30 +942,332 @@ impl GestureDetectionIPC {
 
     /// Internal helper function so that procedures can be defied as closures and executed on the
     /// shared memory space.
-    fn with_buffer(&self, function: fn(MutexGuard<GestureDetectionIPCBuffer>) -> i64) -> i64 {
-        function(self.memory.lock().unwrap())
+    fn with_buffer(&self, function: fn(&GestureDetectionIPCBuffer) -> i64) -> i64 {
+        function(&self.memory)
+    }
+
+    /// Pointer to the x coordinate of the first of `count` points stored from byte `start` of the
+    /// segment, as long as they lie after the fixed fields and inside the mapping. Each point is
+    /// `POINT_FIELDS` consecutive `f64`s.
+    fn coordinates(&self, start: i64, count: usize) -> Result<*mut f64, String> {
+        let end = usize::try_from(start)
+            .ok()
+            .filter(|&start| start >= POINT_ARRAY_OFFSET as usize && start % align_of::<f64>() == 0)
+            .and_then(|start| count.checked_mul(POINT_SIZE).and_then(|length| start.checked_add(length)));
+        match end {
+            Some(end) if end <= self.memory.len() => Ok(self.memory.as_ptr().wrapping_add(start as usize) as *mut f64),
+            _ => Err(format!("{} points at offset {} do not fit in the {} byte segment", count, start, self.memory.len())),
+        }
+    }
+
+    /// Copy `points` into the point array and record where they are. Only the client writes the
+    /// point array, between BEGIN being acknowledged and DETECT being published.
+    pub(crate) fn write_points(&self, points: &[Point]) -> Result<(), String> {
+        if points.len() > POINT_ARRAY_SIZE {
+            return Err(format!("{} points do not fit in the point array (room for {})", points.len(), POINT_ARRAY_SIZE));
+        }
+        let coordinates = self.coordinates(POINT_ARRAY_OFFSET as i64, points.len())?;
+        for (i, point) in points.iter().enumerate() {
+            for (j, field) in point.to_fields().into_iter().enumerate() {
+                unsafe { coordinates.add(POINT_FIELDS * i + j).write(field) };
+            }
+        }
+        self.memory.set_point_array_start(POINT_ARRAY_OFFSET as i64);
+        self.memory.set_point_array_size(points.len() as i64);
+        Ok(())
+    }
+
+    /// Read back the points described by `point_array_start` and `point_array_size`. Both were
+    /// written by the other process, so they are checked before anything is read.
+    pub(crate) fn read_points(&self) -> Result<Vec<Point>, String> {
+        let size = self.memory.point_array_size();
+        let count = usize::try_from(size)
+            .ok()
+            .filter(|&count| count <= POINT_ARRAY_SIZE)
+            .ok_or(format!("Invalid point array size {} (room for {})", size, POINT_ARRAY_SIZE))?;
+        let coordinates = self.coordinates(self.memory.point_array_start(), count)?;
+        Ok((0..count)
+            .map(|i| Point::from_fields(std::array::from_fn(|j| unsafe { coordinates.add(POINT_FIELDS * i + j).read() })))
+            .collect())
+    }
+
+    /// Copy as many templates as fit into the template list, which takes the place of the point
+    /// array, and record how many there are in `point_array_size`. Only the server writes the
+    /// list, in answer to LIST_TEMPLATES.
+    pub(crate) fn write_template_entries(&self, entries: &[TemplateEntry]) {
+        let count = entries.len().min(TEMPLATE_LIST_CAPACITY);
+        for (i, entry) in entries[..count].iter().enumerate() {
+            unsafe { self.template_entry(i).write(*entry) };
+        }
+        self.memory.set_point_array_size(count as i64);
+    }
+
+    /// Read back the list written by `write_template_entries`, checking its size first.
+    pub(crate) fn read_template_entries(&self) -> Result<Vec<TemplateEntry>, String> {
+        let size = self.memory.point_array_size();
+        let count = usize::try_from(size)
+            .ok()
+            .filter(|&count| count <= TEMPLATE_LIST_CAPACITY)
+            .ok_or(format!("Invalid template list size {} (room for {})", size, TEMPLATE_LIST_CAPACITY))?;
+        Ok((0..count).map(|i| unsafe { self.template_entry(i).read() }).collect())
+    }
+
+    /// Copy as many disqualifications as fit into the disqualification list, which takes the place
+    /// of the point array like the template list, and record how many there are in
+    /// `point_array_size`. Only the server writes the list, in answer to LIST_DISQUALIFICATIONS.
+    pub(crate) fn write_disqualification_entries(&self, entries: &[DisqualificationEntry]) {
+        let count = entries.len().min(DISQUALIFICATION_LIST_CAPACITY);
+        for (i, entry) in entries[..count].iter().enumerate() {
+            unsafe { self.disqualification_entry(i).write(*entry) };
+        }
+        self.memory.set_point_array_size(count as i64);
+    }
+
+    /// Read back the list written by `write_disqualification_entries`, checking its size first.
+    pub(crate) fn read_disqualification_entries(&self) -> Result<Vec<DisqualificationEntry>, String> {
+        let size = self.memory.point_array_size();
+        let count = usize::try_from(size)
+            .ok()
+            .filter(|&count| count <= DISQUALIFICATION_LIST_CAPACITY)
+            .ok_or(format!("Invalid disqualification list size {} (room for {})", size, DISQUALIFICATION_LIST_CAPACITY))?;
+        Ok((0..count).map(|i| unsafe { self.disqualification_entry(i).read() }).collect())
+    }
+
+    /// Pointer to the `index`th entry of the disqualification list.
+    fn disqualification_entry(&self, index: usize) -> *mut DisqualificationEntry {
+        self.memory.as_ptr().wrapping_add(POINT_ARRAY_OFFSET as usize + index * DISQUALIFICATION_ENTRY_SIZE) as *mut DisqualificationEntry
+    }
+
+    /// Pointer to the `index`th entry of the template list.
+    fn template_entry(&self, index: usize) -> *mut TemplateEntry {
+        self.memory.as_ptr().wrapping_add(POINT_ARRAY_OFFSET as usize + index * TEMPLATE_ENTRY_SIZE) as *mut TemplateEntry
+    }
+
+    /// Pointer to the slot of the command ring used by the `index`th command ever sent.
+    fn command_slot(&self, index: i64) -> *mut CommandSlot {
+        let slot = index as usize % COMMAND_RING_CAPACITY;
+        self.memory.as_ptr().wrapping_add(POINT_ARRAY_OFFSET as usize + slot * COMMAND_SLOT_SIZE) as *mut CommandSlot
+    }
+
+    /// Append a command to the command ring, waiting up to `timeout` for the server to make room.
+    /// Only the client sends commands.
+    pub(crate) fn push_command(&self, command: &ClientCommand, timeout: Duration) -> Result<(), String> {
+        let head = self.memory.command_head.load(Ordering::Relaxed);
+        let deadline = Instant::now() + timeout;
+        loop {
+            let tail = self.memory.command_tail.load(Ordering::Acquire);
+            if head - tail < COMMAND_RING_CAPACITY as i64 {
+                break;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!("The command ring is still full after {:?}", timeout));
+            }
+            self.doorbell.wait(&self.memory.command_tail, tail, Some(deadline - now));
+        }
+        unsafe { self.command_slot(head).write(command.to_slot()) };
+        self.memory.command_head.store(head + 1, Ordering::Release);
+        self.doorbell.ring(&self.memory.command_head);
+        Ok(())
+    }
+
+    /// Take the oldest command the server has not read yet. Only the server reads commands, and
+    /// it only rings the client when the ring was full, since that is the only time it waits.
+    pub(crate) fn pop_command_slot(&self) -> Option<CommandSlot> {
+        let tail = self.memory.command_tail.load(Ordering::Relaxed);
+        let head = self.memory.command_head.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let slot = unsafe { self.command_slot(tail).read() };
+        self.memory.command_tail.store(tail + 1, Ordering::Release);
+        if head - tail == COMMAND_RING_CAPACITY as i64 {
+            self.doorbell.ring(&self.memory.command_tail);
+        }
+        Some(slot)
+    }
+}
+
+/// Everything the server stores in the segment when a detection completes.
+struct Classification {
+    result: i64,
+    result_parameter: f64,
+    template_id: i64,
+    direction_angle: f64,
+    compass_sector: i64,
+    gesture_result: GestureResult,
+    /// Every gesture type with its score, best first.
+    candidates: Vec<(GenericGestureType, f64)>,
+    /// Why the other gesture types were ruled out, in the order the rules were applied.
+    disqualifications: Vec<Disqualification>,
+}
+
+impl Classification {
+    fn failed(error: DetectionError, touch_points: &[Point]) -> Self {
+        Classification {
+            result: GestureDetectionServer::int_pow2(ERROR_BITWISE_OFFSET) as i64,
+            result_parameter: 0.0,
+            template_id: NO_TEMPLATE,
+            direction_angle: f64::NAN,
+            compass_sector: -1,
+            gesture_result: GestureResult::failed(error, touch_points),
+            candidates: Vec::new(),
+            disqualifications: Vec::new(),
+        }
     }
 }
 
 #[derive(Clone, Debug)]
-struct GestureDetectionServer {
+pub(crate) struct GestureDetectionServer {
     ipc: GestureDetectionIPC,
+    /// The gesture received through the command ring since the last COLLECT.
+    streamed_gesture: Arc<Mutex<GestureDetection>>,
+    /// Turns successive clicks into double and triple taps.
+    tap_sequencer: Arc<Mutex<TapSequencer>>,
+    /// Custom strokes registered by the client, matched before the built-in gesture types.
+    template_recognizer: Arc<Mutex<TemplateRecognizer>>,
+    /// Where the templates are saved whenever they change, if anywhere.
+    template_file: Option<PathBuf>,
+    /// Settings changed by the client through CONFIGURE.
+    configuration: Arc<Mutex<Configuration>>,
+    /// The strategies the client can select through CONFIGURE, indexed by id.
+    detectors: Arc<Mutex<Vec<Arc<dyn GestureDetector>>>>,
+    /// Why the last detection ruled out the gestures it did, for LIST_DISQUALIFICATIONS.
+    disqualifications: Arc<Mutex<Vec<Disqualification>>>,
 }
 
 impl GestureDetectionServer {
+    /// Serve the given segment. The caller is responsible for calling `process_server_actions`.
+    pub(crate) fn new(segment: SharedMemorySegment) -> Self {
+        Self::with_doorbell(segment, Doorbell::Futex)
+    }
+
+    /// Serve the given segment, notifying the client through `doorbell` instead of a futex.
+    pub(crate) fn with_doorbell(segment: SharedMemorySegment, doorbell: Doorbell) -> Self {
+        let template_recognizer = Arc::new(Mutex::new(TemplateRecognizer::default()));
+        // Registered in the order of their ids
+        let detectors: Vec<Arc<dyn GestureDetector>> = vec![
+            Arc::new(RuleBasedDetector),
+            Arc::new(TemplateDetector::new(template_recognizer.clone())),
+            Arc::new(DummyDetector),
+        ];
+        GestureDetectionServer {
+            ipc: GestureDetectionIPC::with_doorbell(segment, doorbell),
+            streamed_gesture: Arc::new(Mutex::new(GestureDetection::new(0, Vec::new(), None))),
+            tap_sequencer: Arc::new(Mutex::new(TapSequencer::default())),
+            template_recognizer,
+            template_file: None,
+            configuration: Arc::new(Mutex::new(Configuration::default())),
+            detectors: Arc::new(Mutex::new(detectors)),
+            disqualifications: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Load the templates saved in `path`, if it exists, and save them there whenever they change.
+    pub(crate) fn with_template_file(mut self, path: &Path) -> Result<Self, String> {
+        *self.template_recognizer.lock().unwrap() = TemplateRecognizer::load(path)?;
+        self.template_file = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    /// Register a custom stroke, returning the id reported in `template_id` when it is matched.
+    pub(crate) fn add_template(&self, name: &str, stroke: &[Point]) -> Result<u32, String> {
+        let mut template_recognizer = self.template_recognizer.lock().unwrap();
+        let id = template_recognizer.add_template(name, stroke)?;
+        self.save_templates(&template_recognizer);
+        Ok(id)
+    }
+
+    /// Forget a template. Returns false if there was none with this id.
+    pub(crate) fn remove_template(&self, id: u32) -> bool {
+        let mut template_recognizer = self.template_recognizer.lock().unwrap();
+        let removed = template_recognizer.remove_template(id);
+        if removed {
+            self.save_templates(&template_recognizer);
+        }
+        removed
+    }
+
+    /// Failing to save is reported but not fatal: the templates stay usable until the server exits.
+    fn save_templates(&self, template_recognizer: &TemplateRecognizer) {
+        if let Some(template_file) = &self.template_file {
+            if let Err(error) = template_recognizer.save(template_file) {
+                println!("Failed to save the templates to {}: {}", template_file.display(), error);
+            }
+        }
+    }
+
+    pub(crate) fn configuration(&self) -> Configuration {
+        *self.configuration.lock().unwrap()
+    }
+
+    /// Change the settings used from the next detection on, or the next point of a stream. Fails,
+    /// keeping the settings it had, if the detector is not registered. New tap thresholds drop
+    /// the tap sequence in progress.
+    pub(crate) fn set_configuration(&self, configuration: Configuration) -> Result<(), String> {
+        if configuration.detector as usize >= self.detectors.lock().unwrap().len() {
+            return Err(format!("Unknown detector {}", configuration.detector));
+        }
+        let previous = std::mem::replace(&mut *self.configuration.lock().unwrap(), configuration);
+        if previous.tap_thresholds != configuration.tap_thresholds {
+            self.tap_sequencer.lock().unwrap().reset();
+        }
+        Ok(())
+    }
+
+    /// Add a detection strategy, returning the id that selects it in the configuration.
+    pub(crate) fn register_detector(&self, detector: Arc<dyn GestureDetector>) -> u32 {
+        let mut detectors = self.detectors.lock().unwrap();
+        detectors.push(detector);
+        (detectors.len() - 1) as u32
+    }
+
+    /// The detector `configuration` selects.
+    fn detector(&self, configuration: &Configuration) -> Arc<dyn GestureDetector> {
+        self.detectors.lock().unwrap()[configuration.detector as usize].clone()
+    }
+
+    /// Why the last detection ruled out the gestures it did, in the order the rules were applied.
+    pub(crate) fn disqualifications(&self) -> Vec<Disqualification> {
+        self.disqualifications.lock().unwrap().clone()
+    }
+
+    /// Mark the server as no longer alive so that clients stop using it.
     pub(crate) fn stop(&self) {
-        todo!()
+        self.set_server_state(Exited);
+        self.ipc.memory.is_alive.store(NOT_ALIVE, Ordering::Release);
     }
 }
 
 impl GestureDetectionServer {
-    /// Used by the server, callable from JNI, to update the server's state.
+    /// Used by the server, callable from JNI, to update the server's state. The release store
+    /// publishes every payload write made before it to the client, which is then woken up.
     pub(crate) fn set_server_state(&self, new_server_state: ServerState) {
-        self.ipc.memory.lock().unwrap().server_state = new_server_state.java_ordinal();
+        self.ipc.memory.server_state.store(new_server_state.java_ordinal(), Ordering::Release);
+        self.ipc.doorbell.ring(&self.ipc.memory.server_state);
+    }
+
+    /// Process client requests until the server exits, sleeping on the doorbell (by default the
+    /// `client_request` futex, or the `command_head` one while streaming) whenever there is
+    /// nothing new to do.
+    pub(crate) fn serve(&self) {
+        loop {
+            // Read the request and the ring before processing them, so that anything published in
+            // between is noticed by the doorbell instead of being slept through.
+            let request = self.ipc.memory.client_request.load(Ordering::Acquire);
+            let command_head = self.ipc.memory.command_head.load(Ordering::Acquire);
+            if !self.process_server_actions() {
+                return;
+            }
+            match self.ipc.memory.server_state() {
+                Streaming => self.ipc.doorbell.wait(&self.ipc.memory.command_head, command_head, Some(STREAM_POLL_INTERVAL)),
+                _ => self.ipc.doorbell.wait(&self.ipc.memory.client_request, request, None),
+            }
+        }
     }
 
     /// Internal helper method to compute the bitwise offset for a result that requires a Direction.
-    fn int_pow2(exponent: u32) -> u32 {
+    fn int_pow2(exponent: u32) -> u64 {
         let mut result = 1;
         for _ in 0..exponent {
             result *= 2;
@@ -285,12 +1276,17 @@ impl GestureDetectionServer {
     }
 
     /// Internal helper method to compute the bitwise offset for a result that requires a Direction.
-    fn directional_offset_for(maybe_direction: Option<DirectionResult>, i: u32) -> u32 {
+    fn directional_offset_for(maybe_direction: Option<DirectionResult>, i: u32) -> u64 {
         match maybe_direction {
             None => { Self::int_pow2(ERROR_BITWISE_OFFSET) }
             Some(direction_result) => {
                 match direction_result {
-                    DirectionResult::Drag(direction) => Self::int_pow2(Direction::java_ordinal(direction) + i),
+                    DirectionResult::Drag(direction) | DirectionResult::MultiDrag(direction, _) => {
+                        Self::int_pow2(Direction::java_ordinal(direction) + i)
+                    }
+                    DirectionResult::AmbiguousDrag(nearest, other) => {
+                        Self::int_pow2(Direction::java_ordinal(nearest) + i) | Self::int_pow2(Direction::java_ordinal(other) + i)
+                    }
                     DirectionResult::Circular(rotation) => Self::int_pow2(RotationDirection::java_ordinal(rotation)),
                     _ => Self::int_pow2(ERROR_BITWISE_OFFSET),
                 }
@@ -301,126 +1297,336 @@ impl GestureDetectionServer {
 
     /// Used by the server, callable from JNI, to run the gesture detection algorithm.
     fn detect_gesture(&self) -> i64 {
-        let mut service_buffer = self.ipc.memory.lock().unwrap();
-
-        service_buffer.fling_direction = DetectingGesture.java_ordinal();
-        let duration = service_buffer.duration;
-        let size = service_buffer.point_array_size;
-        let fling_direction = service_buffer.fling_direction;
-        let first_coordinate = service_buffer.point_array_start;
-        let mut coordinates: [f64; POINT_ARRAY_SIZE] = [-1.0; POINT_ARRAY_SIZE];
-
-        for i in 0..size as usize {
-            coordinates[i] = unsafe {
-                ptr::read(coordinates.as_ptr()
-                    .wrapping_offset((first_coordinate + i as i64) as isize))
-            };
-        }
-
-        println!("Detecting gesture with {} points.", size);
-
-        let mut detection = GestureDetection::new(
-            duration,
-            coordinates.chunks(2).map(|chunk| Point::new(chunk[0], chunk[1])).collect::<Vec<_>>(),
-            if fling_direction == -1 { Option::None } else { Some(Direction::from_index(fling_direction as usize)) },
-        );
-
-        let result = match determine_gesture_dummy(
-            &mut detection.touch_points.clone(),
-            &mut detection,
-        ) {
-            (generic_gesture_type, maybe_direction) => {
-                match generic_gesture_type {
-                    GenericGestureType::Click => Self::int_pow2(CLICK_INDEX_BITWISE_OFFSET),
-                    GenericGestureType::Hold => Self::int_pow2(1),
-                    GenericGestureType::Boomerang => Self::directional_offset_for(maybe_direction, BOOMERANG_BITWISE_OFFSET),
-                    GenericGestureType::Swipe => Self::directional_offset_for(maybe_direction, SWIPE_BITWISE_OFFSET),
-                    GenericGestureType::Circle => {
-                        match maybe_direction {
-                            Some(dr) => match dr {
-                                DirectionResult::Circular(rotation_direction) => {
-                                    match rotation_direction {
-                                        RotationDirection::Clockwise => { Self::int_pow2(CIRCLE_BITWISE_OFFSET) }
-                                        RotationDirection::AntiClockwise => { Self::int_pow2(CIRCLE_BITWISE_OFFSET + 1) }
-                                    }
-                                }
-                                DirectionResult::Drag(_) => Self::int_pow2(ERROR_BITWISE_OFFSET),
-                                DirectionResult::None => Self::int_pow2(ERROR_BITWISE_OFFSET),
-                            },
-                            _ => Self::int_pow2(ERROR_BITWISE_OFFSET)
-                        }
-                    }
-                }
+        let service_buffer = &self.ipc.memory;
+
+        let duration = service_buffer.duration();
+        let fling_direction = service_buffer.fling_direction();
+        let classification = match self.ipc.read_points() {
+            Ok(touch_points) => self.classify(
+                duration,
+                touch_points,
+                if fling_direction == -1 { Option::None } else { Some(Direction::from_index(fling_direction as usize)) },
+                None,
+            ),
+            Err(error) => {
+                println!("Rejecting the point array: {}", error);
+                Classification::failed(DetectionError::InvalidPointArray, &[])
             }
         };
 
-        // Store the result
-        service_buffer.result = result as i64;
+        // Store the result; the caller publishes it by moving to DetectionComplete
+        self.store_classification(&classification);
+        classification.result
+    }
 
-        // Notify the client that the detection is complete
-        service_buffer.server_state = DetectionComplete.java_ordinal();
-        service_buffer.result
+    /// Write every field of a detection's outcome to the segment.
+    fn store_classification(&self, classification: &Classification) {
+        let service_buffer = &self.ipc.memory;
+        service_buffer.set_result(classification.result);
+        service_buffer.set_result_parameter(classification.result_parameter);
+        service_buffer.set_template_id(classification.template_id);
+        service_buffer.set_direction_angle(classification.direction_angle);
+        service_buffer.set_compass_sector(classification.compass_sector);
+        service_buffer.set_result_record(ResultRecord::encode(&classification.gesture_result));
+        service_buffer.set_candidates(encode_candidates(&classification.candidates));
+        *self.disqualifications.lock().unwrap() = classification.disqualifications.clone();
     }
 
-    /// Used by the server, callable from JNI, to check if the client has made a request, process it,
-    /// and update its state and the result if applicable.
-    pub(crate) fn process_server_actions(&self) {
-        let mut service_buffer = self.ipc.memory.lock().unwrap();
-        loop {
-            if service_buffer.is_alive == NOT_ALIVE {
-                panic!("Server is shutting down.");
+    /// Run the gesture detection algorithm on a complete gesture. A click that follows another
+    /// one closely enough is reported as a double or triple tap instead, and a single-finger
+    /// stroke that matches a registered template as that template. `provisional` is the last
+    /// provisional result of a streamed gesture, which a heading close to a sector boundary
+    /// sticks to.
+    fn classify(
+        &self,
+        duration: i64,
+        touch_points: Vec<Point>,
+        fling_direction: Option<Direction>,
+        provisional: Option<(GenericGestureType, DirectionResult)>,
+    ) -> Classification {
+        println!("Detecting gesture with {} points.", touch_points.len());
+
+        let position = touch_points.first().copied().unwrap_or(Point::new(0.0, 0.0));
+        let ended = TapTime {
+            classified: Instant::now(),
+            sampled: touch_points
+                .iter()
+                .filter_map(|point| point.timestamp)
+                .filter(|timestamp| timestamp.is_finite())
+                .reduce(f64::max),
+        };
+        let mut detection = GestureDetection::new(duration as i128, touch_points, fling_direction);
+        // One copy of the settings for the whole detection, so that a CONFIGURE never applies halfway through
+        let configuration = self.configuration();
+        detection.direction_sectors = configuration.direction_sectors;
+        detection.thresholds = configuration.thresholds;
+        detection.display = configuration.display;
+        detection.provisional = provisional;
+
+        let detector = self.detector(&configuration);
+        let (generic_gesture_type, maybe_direction) = detection.detect(detector.as_ref());
+        let position = configuration.display.to_dp(position);
+        let taps = self.tap_sequencer.lock().unwrap().register(&configuration.tap_thresholds, generic_gesture_type, position, ended);
+        let gesture_result = GestureResult {
+            taps,
+            ..GestureResult::detected(
+                generic_gesture_type,
+                maybe_direction.unwrap_or(DirectionResult::None),
+                detection.score(generic_gesture_type),
+                &detection.touch_points,
+            )
+        };
+        let mut classification = Classification {
+            result: Self::encode_result(generic_gesture_type, maybe_direction),
+            result_parameter: Self::encode_result_parameter(maybe_direction),
+            template_id: NO_TEMPLATE,
+            direction_angle: detection.direction_angle.unwrap_or(f64::NAN),
+            compass_sector: detection
+                .direction_angle
+                .map_or(-1, |angle| Direction::compass_sector(angle, configuration.compass_mode) as i64),
+            gesture_result,
+            candidates: detection.candidates(),
+            disqualifications: detection.disqualifications.clone(),
+        };
+        if taps > 1 {
+            classification.result = Self::encode_tap_result(taps);
+            classification.result_parameter = taps as f64;
+            return classification;
+        }
+        let is_stroke = !matches!(generic_gesture_type, GenericGestureType::Click | GenericGestureType::Hold)
+            && detection.tracks().len() == 1;
+        if is_stroke {
+            if let Some((template, score)) = detector.recognize(&detection.touch_points) {
+                println!("Matched template {} with a score of {:.3}.", template.name, score);
+                classification.result = Self::encode_template_result();
+                classification.result_parameter = score;
+                classification.template_id = template.id as i64;
+                classification.gesture_result.template_id = Some(template.id);
+                classification.direction_angle = f64::NAN;
+                classification.compass_sector = -1;
             }
+        }
+        classification
+    }
 
-            let request = service_buffer.client_request;
-
-            match ClientRequest::from_java_ordinal(request) {
-                Ping => {
-                    if(service_buffer.server_state != WaitingForCommand.java_ordinal()) {
-                        return;
-                    }
-                    println!("PING received. Acknowledging.");
-                    service_buffer.server_state = PingAcknowledged.java_ordinal();
+    /// Apply every command the client has added to the command ring, publishing a provisional
+    /// result after each point. A DETECT ends the stream: the result is published like for the
+    /// DETECT request, and anything after it is dropped when the next stream starts.
+    fn process_commands(&self) {
+        let mut streamed_gesture = self.streamed_gesture.lock().unwrap();
+        while let Some(command) = ClientCommand::retrieve_next_command(&self.ipc) {
+            match command {
+                Ok(ClientCommand::Collect { point }) => {
+                    *streamed_gesture = GestureDetection::new(0, Vec::new(), None);
+                    self.add_streamed_point(&mut streamed_gesture, point);
                 }
-                Begin => {
-                    if(service_buffer.server_state != WaitingForCommand.java_ordinal()) {
-                        return;
-                    }
-                    println!("BEGIN received. Initializing gesture detection.");
-                    service_buffer.point_array_size = 0;
-                    self.set_server_state(WaitingForCommand);
+                Ok(ClientCommand::Insert { point }) => self.add_streamed_point(&mut streamed_gesture, point),
+                Ok(ClientCommand::Reset) => {
+                    *streamed_gesture = GestureDetection::new(0, Vec::new(), None);
+                    self.publish_provisional_result(0);
                 }
-                Detect => {
-                    if(service_buffer.server_state != WaitingForArgs.java_ordinal()) {
-                        return;
-                    }
-                    println!("DETECT received. Starting gesture detection.");
+                Ok(ClientCommand::Detect { duration, count, fling_direction }) => {
+                    println!("DETECT command received. Starting gesture detection.");
                     self.set_server_state(DetectingGesture);
-                    self.detect_gesture();
+                    let touch_points = std::mem::take(&mut streamed_gesture.touch_points);
+                    let classification = if touch_points.len() == count as usize {
+                        self.classify(duration as i64, touch_points, fling_direction, streamed_gesture.provisional)
+                    } else {
+                        println!("Expected {} streamed points but received {}.", count, touch_points.len());
+                        Classification::failed(DetectionError::MissingPoints, &touch_points)
+                    };
+                    self.store_classification(&classification);
                     self.set_server_state(DetectionComplete);
+                    return;
                 }
-                Halt => {
-                    println!("HALT received. Exiting.");
-                    let last_server_state = service_buffer.server_state as i32;
-                    self.set_server_state(Exited);
-                    service_buffer.is_alive = NOT_ALIVE;
-                    unsafe { exit(last_server_state); }
+                Err(error) => println!("Ignoring command: {}", error),
+            }
+        }
+    }
+
+    fn add_streamed_point(&self, streamed_gesture: &mut GestureDetection, point: Point) {
+        let configuration = self.configuration();
+        streamed_gesture.direction_sectors = configuration.direction_sectors;
+        streamed_gesture.thresholds = configuration.thresholds;
+        streamed_gesture.display = configuration.display;
+        let (generic_gesture_type, maybe_direction) = streamed_gesture.add_point(point, self.detector(&configuration).as_ref());
+        let result = Self::encode_result(generic_gesture_type, maybe_direction);
+        let confidence = (streamed_gesture.provisional_confidence() * PROVISIONAL_CONFIDENCE_SCALE).round() as i64;
+        self.publish_provisional_result(result | confidence << PROVISIONAL_CONFIDENCE_BITWISE_OFFSET);
+    }
+
+    /// Store a new provisional result, waking a client that is waiting for it to change.
+    fn publish_provisional_result(&self, provisional_result: i64) {
+        let previous = self.ipc.memory.provisional_result.swap(provisional_result, Ordering::Release);
+        if previous != provisional_result {
+            self.ipc.doorbell.ring(&self.ipc.memory.provisional_result);
+        }
+    }
+
+    /// The bitwise result word stored in `result` for a detected gesture and its direction.
+    pub(crate) fn encode_result(generic_gesture_type: GenericGestureType, maybe_direction: Option<DirectionResult>) -> i64 {
+        let result = match generic_gesture_type {
+            GenericGestureType::Click => Self::int_pow2(CLICK_INDEX_BITWISE_OFFSET),
+            GenericGestureType::Hold => Self::int_pow2(1),
+            GenericGestureType::Boomerang => Self::directional_offset_for(maybe_direction, BOOMERANG_BITWISE_OFFSET),
+            GenericGestureType::Swipe => Self::directional_offset_for(maybe_direction, SWIPE_BITWISE_OFFSET),
+            GenericGestureType::Circle => {
+                match maybe_direction {
+                    Some(DirectionResult::Circular(RotationDirection::Clockwise)) => {
+                        Self::int_pow2(CIRCLE_BITWISE_OFFSET)
+                    }
+                    Some(DirectionResult::Circular(RotationDirection::AntiClockwise)) => {
+                        Self::int_pow2(CIRCLE_BITWISE_OFFSET + 1)
+                    }
+                    _ => Self::int_pow2(ERROR_BITWISE_OFFSET)
                 }
-                ClearAcknowledgement => {
-                    if(service_buffer.server_state != DetectionComplete.java_ordinal()
-                      && service_buffer.server_state != PingAcknowledged.java_ordinal()) {
-                        return;
+            }
+            GenericGestureType::Pinch => Self::int_pow2(PINCH_BITWISE_OFFSET),
+            GenericGestureType::Spread => Self::int_pow2(SPREAD_BITWISE_OFFSET),
+            GenericGestureType::Rotate => {
+                match maybe_direction {
+                    Some(DirectionResult::Rotation(RotationDirection::Clockwise, _)) => {
+                        Self::int_pow2(TWO_FINGER_ROTATION_BITWISE_OFFSET)
+                    }
+                    Some(DirectionResult::Rotation(RotationDirection::AntiClockwise, _)) => {
+                        Self::int_pow2(TWO_FINGER_ROTATION_BITWISE_OFFSET + 1)
                     }
-                    println!("CLEAR_ACKNOWLEDGEMENT received. Acknowledging.");
-                    self.set_server_state(WaitingForCommand);
+                    _ => Self::int_pow2(ERROR_BITWISE_OFFSET)
                 }
-                _ => {}
             }
+            GenericGestureType::MultiSwipe => Self::directional_offset_for(maybe_direction, MULTI_SWIPE_BITWISE_OFFSET),
+            GenericGestureType::HoldDrag => Self::directional_offset_for(maybe_direction, HOLD_DRAG_BITWISE_OFFSET),
+        };
+        result as i64
+    }
+
+    /// The bitwise result word stored in `result` for the `taps`th click of a tap sequence.
+    pub(crate) fn encode_tap_result(taps: u32) -> i64 {
+        let result = match taps {
+            0 | 1 => Self::int_pow2(CLICK_INDEX_BITWISE_OFFSET),
+            2 => Self::int_pow2(DOUBLE_TAP_BITWISE_OFFSET),
+            _ => Self::int_pow2(TRIPLE_TAP_BITWISE_OFFSET),
+        };
+        result as i64
+    }
+
+    /// The bitwise result word stored in `result` for a stroke that matched a template. Which
+    /// template is stored in `template_id`.
+    pub(crate) fn encode_template_result() -> i64 {
+        Self::int_pow2(TEMPLATE_BITWISE_OFFSET) as i64
+    }
+
+    /// The value stored in `result_parameter` alongside a result with this direction.
+    pub(crate) fn encode_result_parameter(maybe_direction: Option<DirectionResult>) -> f64 {
+        match maybe_direction {
+            Some(DirectionResult::Scale(scale)) => scale,
+            Some(DirectionResult::Rotation(_, angle)) => angle,
+            Some(DirectionResult::MultiDrag(_, fingers)) => fingers as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Used by the server, callable from JNI, to check if the client has made a request, process it,
+    /// and update its state and the result if applicable. Requests that are not valid in the
+    /// current state are ignored. Returns false once the server has exited.
+    pub(crate) fn process_server_actions(&self) -> bool {
+        let service_buffer = &self.ipc.memory;
+        if !service_buffer.is_alive() {
+            return false;
+        }
+
+        match (service_buffer.client_request(), service_buffer.server_state()) {
+            (Ping, WaitingForCommand) => {
+                println!("PING received. Acknowledging.");
+                self.set_server_state(PingAcknowledged);
+            }
+            (Begin, WaitingForCommand) => {
+                println!("BEGIN received. Initializing gesture detection.");
+                service_buffer.set_point_array_size(0);
+                self.set_server_state(WaitingForArgs);
+            }
+            (Detect, WaitingForArgs) => {
+                println!("DETECT received. Starting gesture detection.");
+                self.set_server_state(DetectingGesture);
+                self.detect_gesture();
+                self.set_server_state(DetectionComplete);
+            }
+            (Stream, WaitingForCommand) => {
+                println!("STREAM received. Accepting commands.");
+                // Drop whatever an abandoned stream left in the ring; the client only sends new
+                // commands once it has seen Streaming.
+                let command_head = service_buffer.command_head.load(Ordering::Acquire);
+                service_buffer.command_tail.store(command_head, Ordering::Release);
+                *self.streamed_gesture.lock().unwrap() = GestureDetection::new(0, Vec::new(), None);
+                self.publish_provisional_result(0);
+                self.set_server_state(Streaming);
+            }
+            (RegisterTemplate, WaitingForArgs) => {
+                println!("REGISTER_TEMPLATE received. Registering the template.");
+                let registered = decode_name(&service_buffer.template_name())
+                    .and_then(|name| self.add_template(&name, &self.ipc.read_points()?));
+                let result = registered.map_or_else(|error| {
+                    println!("Rejecting the template: {}", error);
+                    -1
+                }, |id| id as i64);
+                service_buffer.set_result(result);
+                self.set_server_state(TemplateRequestComplete);
+            }
+            (RemoveTemplate, WaitingForArgs) => {
+                println!("REMOVE_TEMPLATE received. Removing the template.");
+                let id = service_buffer.template_id();
+                let removed = u32::try_from(id).is_ok_and(|id| self.remove_template(id));
+                service_buffer.set_result(if removed { id } else { -1 });
+                self.set_server_state(TemplateRequestComplete);
+            }
+            (ListTemplates, WaitingForArgs) => {
+                println!("LIST_TEMPLATES received. Listing the templates.");
+                let template_recognizer = self.template_recognizer.lock().unwrap();
+                let entries = template_recognizer.templates().iter().map(TemplateEntry::new).collect::<Vec<_>>();
+                self.ipc.write_template_entries(&entries);
+                service_buffer.set_result(entries.len() as i64);
+                self.set_server_state(TemplateRequestComplete);
+            }
+            (ListDisqualifications, WaitingForArgs) => {
+                println!("LIST_DISQUALIFICATIONS received. Listing why the last detection ruled gestures out.");
+                let entries = self.disqualifications().iter().map(DisqualificationEntry::new).collect::<Vec<_>>();
+                self.ipc.write_disqualification_entries(&entries);
+                service_buffer.set_result(entries.len() as i64);
+                self.set_server_state(DebugRequestComplete);
+            }
+            (Configure, WaitingForArgs) => {
+                println!("CONFIGURE received. Applying the configuration.");
+                let result = match Configuration::from_block(&service_buffer.configuration())
+                    .and_then(|configuration| self.set_configuration(configuration))
+                {
+                    Ok(()) => 0,
+                    Err(error) => {
+                        println!("Rejecting the configuration: {}", error);
+                        -1
+                    }
+                };
+                service_buffer.set_configuration(self.configuration().to_block());
+                service_buffer.set_result(result);
+                self.set_server_state(ConfigurationComplete);
+            }
+            (Halt, _) => {
+                println!("HALT received. Exiting.");
+                self.stop();
+                return false;
+            }
+            (_, Streaming) => self.process_commands(),
+            (ClearAcknowledgement, DetectionComplete | PingAcknowledged | TemplateRequestComplete | ConfigurationComplete | DebugRequestComplete) => {
+                println!("CLEAR_ACKNOWLEDGEMENT received. Acknowledging.");
+                self.set_server_state(WaitingForCommand);
+            }
+            _ => {}
         }
+        true
     }
 }
 
 #[derive(Clone, Debug)]
-struct CompositeGestureDetectionClient {
+pub(crate) struct CompositeGestureDetectionClient {
     ipc: GestureDetectionIPC,
 }
 
@@ -429,40 +1635,53 @@ pub struct GestureDetectionDaemon {
     client: CompositeGestureDetectionClient,
     server: GestureDetectionServer,
     thread: Option<JoinHandle<()>>,
-    stop_signal: Arc<Mutex<bool>>, // Signal to stop the thread
 }
 
 impl GestureDetectionDaemon {
+    /// Serve the default segment, with the templates kept in `DEFAULT_TEMPLATE_FILE`.
     pub fn new() -> Self {
-        let buffer = GestureDetectionIPCBuffer::new();
-        let stop_signal = Arc::new(Mutex::new(false));
+        Self::with_template_file(SHARED_MEMORY_NAME, Path::new(DEFAULT_TEMPLATE_FILE))
+    }
 
-        let ipc = GestureDetectionIPC::new(Arc::new(Mutex::new(buffer.clone())));
-        let client = CompositeGestureDetectionClient { ipc: ipc.clone() };
-        let server = GestureDetectionServer { ipc: ipc.clone() };
+    /// Like `with_name`, but the templates registered by clients are loaded from `template_file`
+    /// at startup and saved back to it whenever they change.
+    pub(crate) fn with_template_file(name: &str, template_file: &Path) -> Self {
+        let segment = SharedMemorySegment::create(name).expect("Failed to create the shared memory segment");
+        let server = GestureDetectionServer::new(segment)
+            .with_template_file(template_file)
+            .unwrap_or_else(|error| panic!("Failed to load the templates: {}", error));
+        Self::serve(name, server, Doorbell::Futex)
+    }
 
-        let stop_signal_clone = Arc::clone(&stop_signal);
+    /// Create the named shared memory segment, serve it from a new thread, and attach a client to
+    /// it through a separate mapping, exactly as a client in another process would.
+    pub fn with_name(name: &str) -> Self {
+        Self::with_doorbells(name, Doorbell::Futex, Doorbell::Futex)
+    }
 
-        let thread = Some(thread::spawn(move || {
-            let server = GestureDetectionServer { ipc: GestureDetectionIPC::new(Arc::new(Mutex::new(buffer))).clone() };
-            while !*stop_signal_clone.lock().unwrap() {
-                server.process_server_actions();
-                // Add a small sleep to prevent busy waiting
-                thread::sleep(std::time::Duration::from_millis(100));
-            }
-        }));
+    /// Like `with_name`, but the server and client notify each other through the given doorbells,
+    /// e.g. a pair from `Doorbell::event_fd_pair` or `Doorbell::unix_socket_pair`.
+    pub(crate) fn with_doorbells(name: &str, server_doorbell: Doorbell, client_doorbell: Doorbell) -> Self {
+        let segment = SharedMemorySegment::create(name).expect("Failed to create the shared memory segment");
+        Self::serve(name, GestureDetectionServer::with_doorbell(segment, server_doorbell), client_doorbell)
+    }
+
+    /// Serve the segment `server` was created for from a new thread, and attach a client to it.
+    fn serve(name: &str, server: GestureDetectionServer, client_doorbell: Doorbell) -> Self {
+        let client = CompositeGestureDetectionClient::attach_with_doorbell(name, client_doorbell);
+
+        let thread = Some(CompositeGestureDetectionClient::get_new_thread(server.clone()));
 
         GestureDetectionDaemon {
             client,
             server,
             thread,
-            stop_signal,
         }
     }
     pub fn stop(&mut self) {
         if let Some(thread) = self.thread.take() {
-            // Set the stop signal to true
-            *self.stop_signal.lock().unwrap() = true;
+            // Ask the server to exit; the new request wakes it up
+            self.client.halt();
             // Wait for the thread to finish
             thread.join().expect("Thread panicked during stop");
         }
@@ -470,6 +1689,57 @@ impl GestureDetectionDaemon {
     pub fn detect(&self, duration: i128, fling_direction: i64, points: Vec<Point>) -> i64 {
         self.client.detect(duration, fling_direction, points)
     }
+    pub(crate) fn begin_stream(&self) -> i64 {
+        self.client.begin_stream()
+    }
+    pub(crate) fn send(&self, command: ClientCommand) -> i64 {
+        self.client.send(command)
+    }
+    pub(crate) fn provisional_result(&self) -> Option<(i64, f64)> {
+        self.client.provisional_result()
+    }
+    pub(crate) fn result_parameter(&self) -> f64 {
+        self.client.result_parameter()
+    }
+    pub(crate) fn add_template(&self, name: &str, stroke: &[Point]) -> Result<u32, String> {
+        self.server.add_template(name, stroke)
+    }
+    pub(crate) fn register_detector(&self, detector: Arc<dyn GestureDetector>) -> u32 {
+        self.server.register_detector(detector)
+    }
+    pub(crate) fn template_id(&self) -> Option<u32> {
+        self.client.template_id()
+    }
+    pub(crate) fn direction_angle(&self) -> Option<f64> {
+        self.client.direction_angle()
+    }
+    pub(crate) fn compass_sector(&self) -> Option<u32> {
+        self.client.compass_sector()
+    }
+    pub(crate) fn configure(&self, configuration: ConfigurationBlock) -> i64 {
+        self.client.configure(configuration)
+    }
+    pub(crate) fn configuration(&self) -> ConfigurationBlock {
+        self.client.configuration()
+    }
+    pub(crate) fn result_record(&self) -> Result<GestureResult, String> {
+        self.client.result_record()
+    }
+    pub(crate) fn candidates(&self) -> Result<Vec<(GenericGestureType, f64)>, String> {
+        self.client.candidates()
+    }
+    pub(crate) fn register_template(&self, name: &str, stroke: Vec<Point>) -> i64 {
+        self.client.register_template(name, stroke)
+    }
+    pub(crate) fn remove_template(&self, id: u32) -> i64 {
+        self.client.remove_template(id)
+    }
+    pub(crate) fn list_templates(&self) -> Result<Vec<(u32, String)>, String> {
+        self.client.list_templates()
+    }
+    pub(crate) fn disqualifications(&self) -> Result<Vec<Disqualification>, String> {
+        self.client.disqualifications()
+    }
 }
 
 impl CompositeGestureDetectionClient {
@@ -477,34 +1747,49 @@ impl CompositeGestureDetectionClient {
         CompositeGestureDetectionClient { ipc }
     }
 
-    /// Used by the client, callable from JNI, to start the server.
-    /// This function will start the server in a new thread.
-    pub fn start(address: i64) {
-        let server = GestureDetectionServer {
-            ipc: GestureDetectionIPC::new(
-                Arc::new(
-                    Mutex::new(
-                        unsafe { GestureDetectionIPCBuffer::from_raw(address as *mut c_void) }
-                    )
-                )
-            )
-        };
+    /// Used by the client to map a segment that the server has already created.
+    pub(crate) fn attach(name: &str) -> Self {
+        Self::attach_with_doorbell(name, Doorbell::Futex)
+    }
+
+    /// Like `attach`, but the server is notified through `doorbell` instead of a futex.
+    pub(crate) fn attach_with_doorbell(name: &str, doorbell: Doorbell) -> Self {
+        Self::try_attach_with_doorbell(name, doorbell)
+            .unwrap_or_else(|error| panic!("Failed to attach to {}: {}", name, error))
+    }
 
-        thread::spawn(move || { server.process_server_actions(); });
+    /// Map the named segment and check its header, rejecting servers built for another layout.
+    pub(crate) fn try_attach_with_doorbell(name: &str, doorbell: Doorbell) -> Result<Self, String> {
+        let segment = SharedMemorySegment::open(name).map_err(|errno| errno.to_string())?;
+        segment.validate_header(segment.len()).map_err(|error| error.to_string())?;
+        Ok(Self::new(GestureDetectionIPC::with_doorbell(segment, doorbell)))
+    }
+
+    /// Used by the client, callable from JNI, to start the server.
+    /// This function will start the server in a new thread, operating in place on the segment
+    /// mapped at `address`. The client must have written the header first; a segment laid out
+    /// for another protocol version is rejected.
+    pub(crate) fn start(address: i64) -> Result<(), HeaderError> {
+        let segment = unsafe { SharedMemorySegment::from_raw(address as *mut c_void, SHARED_MEMORY_SIZE) }
+            .expect("Invalid shared memory address");
+        segment.validate_header(segment.len())?;
+        let server = GestureDetectionServer::new(segment);
+
+        Self::get_new_thread(server);
+        Ok(())
     }
 
 
 
     fn get_new_thread(server: GestureDetectionServer) -> JoinHandle<()> {
         thread::spawn(move || {
-            server.process_server_actions();
+            server.serve();
         })
     }
 
     /// Used by the client, callable from JNI, to check if the server is alive.
     pub(crate) fn is_alive(&self) -> bool {
-        self.ping();
-        self.ipc.memory.lock().unwrap().server_state == IS_ALIVE
+        self.ipc.memory.is_alive() && self.ping() != -1
     }
 
     /// Used by the client, callable from JNI, to detect a gesture.
@@ -512,28 +1797,18 @@ impl CompositeGestureDetectionClient {
     /// detection request to the server and wait for the result.
     pub fn detect(&self, duration: i128, fling_direction: i64, points: Vec<Point>) -> i64 {
         println!("Detecting gesture with {} points.", points.len());
-        let mut service_buffer = self.ipc.memory.lock().unwrap();
-        println!("a");
-        service_buffer.point_array_size = points.len() as i64;
-        println!("b");
-        service_buffer.point_array_start = SharedMemoryOffset::PointArrayStart as i64;
-        println!("c");
-        for (i, point) in points.iter().enumerate() {
-            unsafe {
-                ptr::write(
-                    &mut ((service_buffer.point_array_start as f64).clone() + (i * 2) as f64),
-                    point.x,
-                );
-                ptr::write(
-                    &mut ((service_buffer.point_array_start as f64).clone() + (i * 2 + 1) as f64),
-                    point.y,
-                );
-            }
+        if points.len() > POINT_ARRAY_SIZE {
+            println!("Too many points: the point array has room for {}.", POINT_ARRAY_SIZE);
+            return -1;
         }
-        println!("d");
-        service_buffer.duration = duration;
-        service_buffer.fling_direction = fling_direction;
-        println!("e");
+        if self.acknowledge_begin() == -1 {
+            return -1;
+        }
+
+        // The server is waiting for arguments, so the payload is ours until DETECT is published
+        let service_buffer = &self.ipc.memory;
+        self.ipc.write_points(&points).expect("The point count was checked before BEGIN");
+        service_buffer.set_duration(duration as i64);
         let fling_direction = if points.len() > 1 {
             let last_point = points[points.len() - 1];
             let second_to_last_point = points[points.len() - 2];
@@ -543,60 +1818,244 @@ impl CompositeGestureDetectionClient {
         } else {
             Direction::North
         };
-        println!("f");
-        service_buffer.fling_direction = fling_direction.java_ordinal() as i64;
-        println!("g");
-        self.block_until(Detect, DetectionComplete)
+        service_buffer.set_fling_direction(fling_direction.java_ordinal() as i64);
+
+        let result = self.block_until(Detect, DetectionComplete);
+        self.clear_acknowledgement();
+        result
+    }
+
+    /// Used by the client to send the next gesture point by point as the finger moves, through
+    /// `send`, instead of all at once through `detect`.
+    pub(crate) fn begin_stream(&self) -> i64 {
+        self.block_until(Stream, Streaming)
+    }
+
+    /// Used by the client to add a command to a stream started by `begin_stream`. Points are
+    /// queued without waiting for the server. A DETECT waits for and returns the result like
+    /// `detect` does, and ends the stream.
+    pub(crate) fn send(&self, command: ClientCommand) -> i64 {
+        if let Err(error) = self.ipc.push_command(&command, REQUEST_TIMEOUT) {
+            println!("{} failed: {}", command.name(), error);
+            return -1;
+        }
+        match command {
+            ClientCommand::Detect { .. } => {
+                let result = self.await_server_state(DetectionComplete);
+                self.clear_acknowledgement();
+                result
+            }
+            _ => 0,
+        }
+    }
+
+    /// Used by the client while streaming to read the result the gesture would have if the
+    /// finger were lifted now, and how sure the server is of it (from 0 to 1). None until the
+    /// server has classified the first point. Only a DETECT command confirms the result.
+    pub(crate) fn provisional_result(&self) -> Option<(i64, f64)> {
+        match self.ipc.memory.provisional_result.load(Ordering::Acquire) {
+            0 => None,
+            provisional_result => Some((
+                provisional_result & ((1 << PROVISIONAL_CONFIDENCE_BITWISE_OFFSET) - 1),
+                (provisional_result >> PROVISIONAL_CONFIDENCE_BITWISE_OFFSET) as f64 / PROVISIONAL_CONFIDENCE_SCALE,
+            )),
+        }
+    }
+
+    /// Used by the client after a detection to read the measurement that goes with a multi-touch,
+    /// multi-tap or template result: the scale factor of a pinch or spread, the angle of a
+    /// rotation in degrees, the number of fingers of a swipe, the number of taps, or the score of
+    /// a template match from 0 to 1. Zero for other results, and valid until the next detection.
+    pub(crate) fn result_parameter(&self) -> f64 {
+        self.ipc.memory.result_parameter()
+    }
+
+    /// Used by the client after a detection to read which registered template the gesture
+    /// matched, if any. Valid until the next detection.
+    pub(crate) fn template_id(&self) -> Option<u32> {
+        match self.ipc.memory.template_id() {
+            NO_TEMPLATE => None,
+            template_id => Some(template_id as u32),
+        }
+    }
+
+    /// Used by the client after a detection to read the heading of a swipe, boomerang,
+    /// hold-then-drag or multi-finger swipe in degrees, anticlockwise from East with North up the
+    /// screen. None for other results, and valid until the next detection.
+    pub(crate) fn direction_angle(&self) -> Option<f64> {
+        Some(self.ipc.memory.direction_angle()).filter(|angle| !angle.is_nan())
+    }
+
+    /// Used by the client after a detection to read the sector `direction_angle` falls in: a
+    /// `Direction` ordinal, or a `CompassPoint` ordinal if the configured `CompassMode` is
+    /// `SixteenPoint`. None whenever `direction_angle` is.
+    pub(crate) fn compass_sector(&self) -> Option<u32> {
+        u32::try_from(self.ipc.memory.compass_sector()).ok()
+    }
+
+    /// Used by the client after a detection to read its outcome as a structured record instead of
+    /// the bits of `result`. Valid until the next detection.
+    pub(crate) fn result_record(&self) -> Result<GestureResult, String> {
+        self.ipc.memory.result_record().decode()
+    }
+
+    /// Used by the client after a detection to read the gesture types it could have been, best
+    /// first, with their scores from 0 to 1. A gesture that no rule ruled out scores 1, so more
+    /// than one candidate scoring 1 means the result is ambiguous. Valid until the next detection.
+    pub(crate) fn candidates(&self) -> Result<Vec<(GenericGestureType, f64)>, String> {
+        decode_candidates(&self.ipc.memory.candidates())
+    }
+
+    /// Used by the client to register a custom stroke under `name` through the point array.
+    /// Returns the template's id, or -1 if the name or the stroke was rejected. The server keeps
+    /// it in its template file, so it only needs registering once.
+    pub(crate) fn register_template(&self, name: &str, stroke: Vec<Point>) -> i64 {
+        let name = match encode_name(name) {
+            Ok(name) => name,
+            Err(error) => {
+                println!("{}", error);
+                return -1;
+            }
+        };
+        if stroke.len() > POINT_ARRAY_SIZE {
+            println!("Too many points: the point array has room for {}.", POINT_ARRAY_SIZE);
+            return -1;
+        }
+        if self.acknowledge_begin() == -1 {
+            return -1;
+        }
+        self.ipc.write_points(&stroke).expect("The point count was checked before BEGIN");
+        self.ipc.memory.set_template_name(name);
+        let result = self.block_until(RegisterTemplate, TemplateRequestComplete);
+        self.clear_acknowledgement();
+        result
+    }
+
+    /// Used by the client to forget a template. Returns its id, or -1 if there was none.
+    pub(crate) fn remove_template(&self, id: u32) -> i64 {
+        if self.acknowledge_begin() == -1 {
+            return -1;
+        }
+        self.ipc.memory.set_template_id(id as i64);
+        let result = self.block_until(RemoveTemplate, TemplateRequestComplete);
+        self.clear_acknowledgement();
+        result
+    }
+
+    /// Used by the client to read the ids and names of the registered templates. At most
+    /// `TEMPLATE_LIST_CAPACITY` of them fit in the segment.
+    pub(crate) fn list_templates(&self) -> Result<Vec<(u32, String)>, String> {
+        if self.acknowledge_begin() == -1 {
+            return Err(String::from("The server did not acknowledge BEGIN"));
+        }
+        let listed = match self.block_until(ListTemplates, TemplateRequestComplete) {
+            -1 => Err(String::from("The server did not answer LIST_TEMPLATES")),
+            _ => self.ipc.read_template_entries().and_then(|entries| {
+                entries.iter().map(|entry| Ok((entry.id as u32, entry.name()?))).collect()
+            }),
+        };
+        self.clear_acknowledgement();
+        listed
     }
 
-    fn ping(&self) {
-        self.block_until(Ping, PingAcknowledged);
+    /// Used by the client to find out why the last detection ruled out the gestures it did, e.g.
+    /// why a circle came out as a boomerang. At most `DISQUALIFICATION_LIST_CAPACITY` of them fit
+    /// in the segment.
+    pub(crate) fn disqualifications(&self) -> Result<Vec<Disqualification>, String> {
+        if self.acknowledge_begin() == -1 {
+            return Err(String::from("The server did not acknowledge BEGIN"));
+        }
+        let listed = match self.block_until(ListDisqualifications, DebugRequestComplete) {
+            -1 => Err(String::from("The server did not answer LIST_DISQUALIFICATIONS")),
+            _ => self.ipc.read_disqualification_entries().and_then(|entries| {
+                entries.iter().map(DisqualificationEntry::disqualification).collect()
+            }),
+        };
+        self.clear_acknowledgement();
+        listed
+    }
+
+    /// Used by the client to change the detector's settings. Returns 0 if the server applied them,
+    /// or -1 if it rejected them and kept the settings it had.
+    pub(crate) fn configure(&self, configuration: ConfigurationBlock) -> i64 {
+        if self.acknowledge_begin() == -1 {
+            return -1;
+        }
+        self.ipc.memory.set_configuration(configuration);
+        let result = self.block_until(Configure, ConfigurationComplete);
+        self.clear_acknowledgement();
+        result
+    }
+
+    /// Used by the client after CONFIGURE to read the settings the server is using.
+    pub(crate) fn configuration(&self) -> ConfigurationBlock {
+        self.ipc.memory.configuration()
+    }
+
+    fn ping(&self) -> i64 {
+        let result = self.block_until(Ping, PingAcknowledged);
+        self.clear_acknowledgement();
+        result
     }
 
-    fn acknowledge_begin(&self) {
-        self.block_until(Begin, WaitingForArgs);
+    fn acknowledge_begin(&self) -> i64 {
+        self.block_until(Begin, WaitingForArgs)
     }
 
+    fn clear_acknowledgement(&self) -> i64 {
+        self.block_until(ClearAcknowledgement, WaitingForCommand)
+    }
+
+    /// Used by the client to stop the server. The server marks itself as not alive on the way out.
+    pub(crate) fn halt(&self) -> i64 {
+        self.set_client_request(Halt);
+        // A streaming server sleeps on the command ring instead of on `client_request`
+        self.ipc.doorbell.ring(&self.ipc.memory.command_head);
+        self.await_server_state(Exited)
+    }
+
+    /// The release store publishes every payload write made before it to the server, which is
+    /// then woken up.
     fn set_client_request(&self, request: ClientRequest) {
-        print!("set_client_request");
-        let mut service_buffer = self.ipc.memory.lock().unwrap();
-        print!("got the buffer");
-        service_buffer.client_request = request.java_ordinal();
-        print!("set the request");
+        self.ipc.memory.client_request.store(request.java_ordinal(), Ordering::Release);
+        self.ipc.doorbell.ring(&self.ipc.memory.client_request);
     }
 
     fn block_until(&self, request: ClientRequest, desired_server_state: ServerState) -> i64 {
-        println!("Blocking until {:?} is acknowledged.", desired_server_state);
+        println!("Blocking until {} is acknowledged.", request.name());
         self.set_client_request(request);
-        print!("f");
-        let start_time = Instant::now();
-        print!("g");
+        self.await_server_state(desired_server_state)
+    }
+
+    fn await_server_state(&self, desired_server_state: ServerState) -> i64 {
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
         let mut did_timeout = false;
-        println!("server_state: {:?}, desired_server_state: {:?}", self.ipc.memory.lock().unwrap().server_state, desired_server_state.java_ordinal());
-        while self.ipc.memory.lock().unwrap().server_state != desired_server_state.java_ordinal() {
-            print!("f");
-            thread::sleep(Duration::from_micros(100));
-            print!("g");
-            if start_time.elapsed().as_millis() > 40 {
-                print!("TIMEOUT TIMEOUT TIMEOUT TIMEOUT TIMEOUT TIMEOUT TIMEOUT TIMEOUT.");
+        loop {
+            let server_state = self.ipc.memory.server_state.load(Ordering::Acquire);
+            if server_state == desired_server_state.java_ordinal() {
+                break;
+            }
+            let now = Instant::now();
+            if now >= deadline {
                 did_timeout = true;
                 break;
             }
+            self.ipc.doorbell.wait(&self.ipc.memory.server_state, server_state, Some(deadline - now));
         }
-        print!("h");
         if did_timeout {
-            println!("Detection timed out.");
+            println!("Waiting for {} timed out.", desired_server_state.name());
             -1
         } else {
-            println!(
-                "Detection complete: {:?}",
-                GenericGestureType::from_non_generic_gesture_int_value(self.ipc.memory.lock().unwrap().result as u8)
-                    .unwrap()
-                    .name()
-            );
             match desired_server_state {
-                    Exited => -1,
-                    DetectionComplete => self.ipc.memory.lock().unwrap().result,
+                Exited => -1,
+                TemplateRequestComplete | ConfigurationComplete | DebugRequestComplete => self.ipc.memory.result(),
+                DetectionComplete => {
+                    println!(
+                        "Detection complete: {:?}",
+                        self.ipc.memory.result_record().decode().map(|gesture_result| gesture_result.gesture.map(|gesture| gesture.name()))
+                    );
+                    self.ipc.memory.result()
+                }
                 _ => desired_server_state.java_ordinal()
             }
         }
@@ -1,4 +1,5 @@
 use std::f64::consts::PI;
+use crate::core::compass_point::CompassPoint;
 use crate::core::point::Point;
 
 #[derive(Clone, Copy, Debug)]
@@ -73,6 +74,15 @@ impl Direction {
         Self::direction_from_alpha(alpha)
     }
 
+    /// The ordinal of the sector `alpha` falls in on the compass `compass_mode` selects: a
+    /// `Direction` on 8 points, a `CompassPoint` on 16.
+    pub(crate) fn compass_sector(alpha: f64, compass_mode: CompassMode) -> u32 {
+        match compass_mode {
+            CompassMode::EightPoint => Self::direction_from_alpha(alpha.rem_euclid(360.0)).java_ordinal(),
+            CompassMode::SixteenPoint => CompassPoint::from_alpha(alpha).java_ordinal(),
+        }
+    }
+
     pub(crate) fn direction_from_alpha(alpha: f64) -> Direction {
         let sectors: Vec<(usize, f64)> = (0..8)
             .map(|i| (i, (360.0 / 8.0) * (i as f64 + 1.0) - (360.0 / 16.0)))
@@ -118,6 +128,12 @@ impl Direction {
         (360.0 + (dy.atan2(dx) * 180.0 / PI)) % 360.0
     }
 
+    /// Wrap an angle in degrees into the range (-180, 180], e.g. the difference of two headings.
+    pub(crate) fn normalize_degrees(degrees: f64) -> f64 {
+        let wrapped = degrees.rem_euclid(360.0);
+        if wrapped > 180.0 { wrapped - 360.0 } else { wrapped }
+    }
+
     pub(crate) fn values() -> Vec<Self> {
         vec![
             Self::East,
@@ -132,6 +148,43 @@ impl Direction {
     }
 
 }
+
+/// How finely the heading of a directional result is reported in `compass_sector`: in the 8
+/// directions of `Direction`, or on the 16 points of `CompassPoint`. The direction bits of
+/// `result` are the same 8 either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum CompassMode {
+    #[default]
+    EightPoint,
+    SixteenPoint,
+}
+
+impl CompassMode {
+    pub(crate) fn name(self) -> String {
+        String::from(
+            match self {
+                CompassMode::EightPoint => "EIGHT_POINT",
+                CompassMode::SixteenPoint => "SIXTEEN_POINT",
+            }
+        )
+    }
+
+    pub(crate) fn java_ordinal(self) -> i64 {
+        match self {
+            CompassMode::EightPoint => 0,
+            CompassMode::SixteenPoint => 1,
+        }
+    }
+
+    pub(crate) fn from_java_ordinal(ordinal: i64) -> Option<Self> {
+        match ordinal {
+            0 => Some(CompassMode::EightPoint),
+            1 => Some(CompassMode::SixteenPoint),
+            _ => None,
+        }
+    }
+}
+
 impl PartialEq for Direction {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -1,9 +1,18 @@
 use crate::core::direction::Direction;
 use crate::core::rotation_direction::RotationDirection;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub(crate) enum DirectionResult {
     Circular(RotationDirection),
     Drag(Direction),
+    /// Heading of a stroke too close to the boundary between two directions to tell them apart,
+    /// the nearer one first. Only reported with `BoundaryPolicy::Ambiguous`.
+    AmbiguousDrag(Direction, Direction),
+    /// Direction of a multi-finger swipe and the number of fingers.
+    MultiDrag(Direction, u8),
+    /// Direction of a two-finger rotation and the angle turned, in degrees.
+    Rotation(RotationDirection, f64),
+    /// Factor the distance between the fingers changed by: below 1 for a pinch, above for a spread.
+    Scale(f64),
     None,
 }
\ No newline at end of file
@@ -0,0 +1,108 @@
+use crate::core::direction::Direction;
+use crate::core::direction_result::DirectionResult;
+
+/// Widest dead zone on either side of a boundary: half a sector, at which every heading is
+/// close to a boundary.
+pub(crate) const MAXIMUM_DEAD_ZONE: f64 = 22.5;
+
+/// What a heading inside the dead zone around the boundary between two directions becomes,
+/// unless the stroke was already heading one of those two ways.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BoundaryPolicy {
+    /// The direction of the sector the heading is in, as without a dead zone.
+    Nearest,
+    /// Both directions, reported as `DirectionResult::AmbiguousDrag`.
+    Ambiguous,
+    /// Whichever of the two directions is North, East, South or West.
+    Cardinal,
+}
+
+impl BoundaryPolicy {
+    pub(crate) fn name(self) -> String {
+        String::from(
+            match self {
+                BoundaryPolicy::Nearest => "NEAREST",
+                BoundaryPolicy::Ambiguous => "AMBIGUOUS",
+                BoundaryPolicy::Cardinal => "CARDINAL",
+            }
+        )
+    }
+
+    pub(crate) fn java_ordinal(self) -> i64 {
+        match self {
+            BoundaryPolicy::Nearest => 0,
+            BoundaryPolicy::Ambiguous => 1,
+            BoundaryPolicy::Cardinal => 2,
+        }
+    }
+
+    pub(crate) fn from_java_ordinal(ordinal: i64) -> Option<Self> {
+        match ordinal {
+            0 => Some(BoundaryPolicy::Nearest),
+            1 => Some(BoundaryPolicy::Ambiguous),
+            2 => Some(BoundaryPolicy::Cardinal),
+            _ => None,
+        }
+    }
+}
+
+/// How headings are bucketed into the 8 directions of a stroke. The 45 degree sectors of
+/// `Direction::direction_from_alpha` meet at 22.5, 67.5 ... degrees; within `dead_zone` degrees
+/// of one of those boundaries the heading is resolved by `boundary_policy`. A stroke that was
+/// already heading one of the two ways while it was streamed keeps that direction, so that its
+/// result does not flip back and forth while the finger follows the boundary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DirectionSectors {
+    dead_zone: f64,
+    boundary_policy: BoundaryPolicy,
+}
+
+impl Default for DirectionSectors {
+    fn default() -> Self {
+        DirectionSectors { dead_zone: 0.0, boundary_policy: BoundaryPolicy::Nearest }
+    }
+}
+
+impl DirectionSectors {
+    /// Fails if `dead_zone` is not between 0 and `MAXIMUM_DEAD_ZONE` degrees.
+    pub(crate) fn new(dead_zone: f64, boundary_policy: BoundaryPolicy) -> Result<Self, String> {
+        if !(0.0..=MAXIMUM_DEAD_ZONE).contains(&dead_zone) {
+            return Err(format!("Dead zone {} is not between 0 and {} degrees", dead_zone, MAXIMUM_DEAD_ZONE));
+        }
+        Ok(DirectionSectors { dead_zone, boundary_policy })
+    }
+
+    pub(crate) fn dead_zone(&self) -> f64 {
+        self.dead_zone
+    }
+
+    pub(crate) fn boundary_policy(&self) -> BoundaryPolicy {
+        self.boundary_policy
+    }
+
+    /// The direction of a stroke heading `alpha` degrees, as returned by `Direction::angle_from`.
+    /// `previous` is the direction the stroke was reported with before its last point, if any.
+    pub(crate) fn drag(&self, alpha: f64, previous: Option<Direction>) -> DirectionResult {
+        let alpha = alpha.rem_euclid(360.0);
+        let nearest = Direction::direction_from_alpha(alpha);
+        // Sector i runs from boundary i - 1 to boundary i, centred on 45i degrees
+        let boundary = ((alpha - 22.5) / 45.0).round();
+        if (alpha - (45.0 * boundary + 22.5)).abs() > self.dead_zone {
+            return DirectionResult::Drag(nearest);
+        }
+        let (below, above) = (
+            Direction::from_index(boundary.rem_euclid(8.0) as usize),
+            Direction::from_index((boundary + 1.0).rem_euclid(8.0) as usize),
+        );
+        if let Some(previous) = previous.filter(|&previous| previous == below || previous == above) {
+            return DirectionResult::Drag(previous);
+        }
+        match self.boundary_policy {
+            BoundaryPolicy::Nearest => DirectionResult::Drag(nearest),
+            BoundaryPolicy::Ambiguous if nearest == below => DirectionResult::AmbiguousDrag(below, above),
+            BoundaryPolicy::Ambiguous => DirectionResult::AmbiguousDrag(above, below),
+            BoundaryPolicy::Cardinal if below.java_ordinal() % 2 == 0 => DirectionResult::Drag(below),
+            BoundaryPolicy::Cardinal => DirectionResult::Drag(above),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::core::point::Point;
+
+/// Density at which a density-independent pixel is one pixel, Android's baseline (mdpi).
+pub(crate) const BASELINE_DENSITY: f64 = 160.0;
+
+/// The display the client's coordinates are in. The distance thresholds of the detectors are in
+/// density-independent pixels (dp), so that a drag is as long on a 560 dpi phone as on a 160 dpi
+/// tablet, and touch points are converted to dp before they are classified. Until a client says
+/// otherwise the display is at `BASELINE_DENSITY`, where dp and pixels are the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Display {
+    density: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display { density: BASELINE_DENSITY, width: 0.0, height: 0.0 }
+    }
+}
+
+impl Display {
+    /// `density` in dots per inch, `width` and `height` in pixels, with 0 for a size that is not
+    /// known. Fails if the density is not positive or the size is negative.
+    pub(crate) fn new(density: f64, width: f64, height: f64) -> Result<Self, String> {
+        if !(density > 0.0 && density.is_finite()) {
+            return Err(format!("Display density {} is not a positive number of dots per inch", density));
+        }
+        if !(width >= 0.0 && width.is_finite() && height >= 0.0 && height.is_finite()) {
+            return Err(format!("Display size {}x{} is not a size in pixels", width, height));
+        }
+        Ok(Display { density, width, height })
+    }
+
+    pub(crate) fn density(&self) -> f64 {
+        self.density
+    }
+
+    pub(crate) fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// `pixels` in density-independent pixels.
+    pub(crate) fn dp(&self, pixels: f64) -> f64 {
+        pixels * BASELINE_DENSITY / self.density
+    }
+
+    /// `dp` in millimetres on the screen.
+    pub(crate) fn millimetres(&self, dp: f64) -> f64 {
+        dp / BASELINE_DENSITY * 25.4
+    }
+
+    /// Length of the diagonal in dp, if the size is known.
+    pub(crate) fn diagonal(&self) -> Option<f64> {
+        (self.width > 0.0 && self.height > 0.0).then(|| self.dp(self.width.hypot(self.height)))
+    }
+
+    /// `point` with its coordinates in dp, keeping its timestamp, pressure and pointer.
+    pub(crate) fn to_dp(self, point: Point) -> Point {
+        Point { x: self.dp(point.x), y: self.dp(point.y), ..point }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::core::generic_gesture_type::{GenericGestureType, ALL_GESTURE_TYPES_POSSIBLE};
+use std::fmt;
+
+/// The checks the rule-based detectors rule gestures out with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Rule {
+    /// Number of pointers, against the 2 it takes for a multi-touch gesture.
+    PointerCount,
+    /// Furthest distance in dp from the first point, against the minimum drag length.
+    DragLength,
+    /// Duration in milliseconds, against the hold threshold.
+    HoldDuration,
+    /// Milliseconds held still before dragging, against the hold threshold. NaN without timestamps.
+    HoldBeforeDrag,
+    /// Mean distance in dp from the centroid, against the minimum circle radius.
+    CircleRadius,
+    /// Sharpest turn in degrees, against the angle that makes it a reversal.
+    Reversal,
+    /// Degrees the return leg of a reversal strays from retracing the outbound leg.
+    BoomerangReturn,
+    /// Total turning in degrees, against the turning that makes a circle.
+    CircleTurning,
+    /// Total turning in degrees, against the most a swipe may turn.
+    SwipeTurning,
+    /// Distance in dp the fingers moved together, against the minimum drag length.
+    MultiSwipeDistance,
+    /// Degrees the fingers turned around their centroid, against the minimum rotation.
+    RotationAngle,
+    /// Factor the fingers spread apart or closed in by, against the minimum scale factor.
+    ScaleFactor,
+    /// The stronger of the rotation and the scale factor, as a multiple of its threshold, against 1.
+    MultiTouchMovement,
+}
+
+impl Rule {
+    pub(crate) fn name(self) -> String {
+        String::from(
+            match self {
+                Rule::PointerCount => "POINTER_COUNT",
+                Rule::DragLength => "DRAG_LENGTH",
+                Rule::HoldDuration => "HOLD_DURATION",
+                Rule::HoldBeforeDrag => "HOLD_BEFORE_DRAG",
+                Rule::CircleRadius => "CIRCLE_RADIUS",
+                Rule::Reversal => "REVERSAL",
+                Rule::BoomerangReturn => "BOOMERANG_RETURN",
+                Rule::CircleTurning => "CIRCLE_TURNING",
+                Rule::SwipeTurning => "SWIPE_TURNING",
+                Rule::MultiSwipeDistance => "MULTI_SWIPE_DISTANCE",
+                Rule::RotationAngle => "ROTATION_ANGLE",
+                Rule::ScaleFactor => "SCALE_FACTOR",
+                Rule::MultiTouchMovement => "MULTI_TOUCH_MOVEMENT",
+            }
+        )
+    }
+
+    pub(crate) fn java_ordinal(self) -> i64 {
+        Self::values().iter().position(|&rule| rule == self).unwrap() as i64
+    }
+
+    pub(crate) fn from_java_ordinal(ordinal: i64) -> Option<Self> {
+        usize::try_from(ordinal).ok().and_then(|index| Self::values().get(index).copied())
+    }
+
+    /// Bits of the gestures the rule tells apart, as in `GestureDetection::possible_gestures`.
+    /// Passing the rule only counts as evidence for these.
+    pub(crate) fn gestures(self) -> u16 {
+        use GenericGestureType::*;
+        let bits = |gestures: &[GenericGestureType]| gestures.iter().fold(0, |bits, gesture| bits | gesture.java_ordinal());
+        match self {
+            Rule::PointerCount => ALL_GESTURE_TYPES_POSSIBLE,
+            Rule::DragLength => bits(&[Click, Hold, Swipe, Circle, Boomerang, HoldDrag]),
+            Rule::HoldDuration => bits(&[Click, Hold]),
+            Rule::HoldBeforeDrag => bits(&[Swipe, Circle, Boomerang, HoldDrag]),
+            Rule::CircleRadius => bits(&[Circle]),
+            Rule::Reversal => bits(&[Swipe, Boomerang]),
+            Rule::BoomerangReturn => bits(&[Boomerang, Circle]),
+            Rule::CircleTurning | Rule::SwipeTurning => bits(&[Swipe, Circle, Boomerang]),
+            Rule::MultiSwipeDistance => bits(&[MultiSwipe]),
+            Rule::RotationAngle => bits(&[Rotate]),
+            Rule::ScaleFactor => bits(&[Pinch, Spread]),
+            Rule::MultiTouchMovement => bits(&[Click, Hold]),
+        }
+    }
+
+    pub(crate) fn values() -> Vec<Self> {
+        vec![
+            Self::PointerCount,
+            Self::DragLength,
+            Self::HoldDuration,
+            Self::HoldBeforeDrag,
+            Self::CircleRadius,
+            Self::Reversal,
+            Self::BoomerangReturn,
+            Self::CircleTurning,
+            Self::SwipeTurning,
+            Self::MultiSwipeDistance,
+            Self::RotationAngle,
+            Self::ScaleFactor,
+            Self::MultiTouchMovement,
+        ]
+    }
+}
+
+/// Why some gestures were ruled out: the rule, what it measured and the threshold it compared
+/// the measurement with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Disqualification {
+    pub(crate) rule: Rule,
+    pub(crate) measured: f64,
+    pub(crate) threshold: f64,
+    /// Bits of the gestures ruled out, as in `GestureDetection::possible_gestures`.
+    pub(crate) gestures: u16,
+}
+
+impl Disqualification {
+    /// The gesture types ruled out, in ordinal order.
+    pub(crate) fn gesture_types(&self) -> Vec<GenericGestureType> {
+        GenericGestureType::values().into_iter().filter(|gesture| self.gestures & gesture.java_ordinal() != 0).collect()
+    }
+
+    /// How far the measurement landed from the threshold, as a share of the threshold from 0
+    /// (right on it) to 1 (twice or more as far). Measurements are compared by magnitude, and
+    /// scale factors by their logarithm, so that halving counts as much as doubling. Pointer
+    /// counts and missing measurements are never borderline.
+    pub(crate) fn margin(&self) -> f64 {
+        let (measured, threshold) = match self.rule {
+            Rule::PointerCount => return 1.0,
+            Rule::ScaleFactor => (self.measured.ln().abs(), self.threshold.ln().abs()),
+            _ => (self.measured.abs(), self.threshold.abs()),
+        };
+        let margin = (measured - threshold).abs() / threshold;
+        if margin.is_nan() { 1.0 } else { margin.min(1.0) }
+    }
+
+    /// What the rule says about `gesture`, from 0 to 1: above 0.5 if the gesture passed it and
+    /// below if it was ruled out, the further the more decisively. None if the rule neither ruled
+    /// the gesture out nor tells it apart from others.
+    pub(crate) fn evidence(&self, gesture: GenericGestureType) -> Option<f64> {
+        if self.gestures & gesture.java_ordinal() != 0 {
+            Some((1.0 - self.margin()) / 2.0)
+        } else if self.rule.gestures() & gesture.java_ordinal() != 0 {
+            Some((1.0 + self.margin()) / 2.0)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Disqualification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gestures = self.gesture_types().iter().map(|gesture| gesture.name()).collect::<Vec<_>>();
+        write!(
+            f,
+            "{} measured {:.2} against {:.2}, ruling out {}",
+            self.rule.name(),
+            self.measured,
+            self.threshold,
+            gestures.join(", ")
+        )
+    }
+}
+
+/// `Disqualification` as stored in the segment by LIST_DISQUALIFICATIONS.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DisqualificationEntry {
+    /// `Rule` ordinal.
+    pub(crate) rule: i64, // offset=0, +8
+    pub(crate) gestures: i64, // offset=8, +8
+    pub(crate) measured: f64, // offset=16, +8
+    pub(crate) threshold: f64, // offset=24, +8
+}
+
+impl DisqualificationEntry {
+    pub(crate) fn new(disqualification: &Disqualification) -> Self {
+        DisqualificationEntry {
+            rule: disqualification.rule.java_ordinal(),
+            gestures: disqualification.gestures as i64,
+            measured: disqualification.measured,
+            threshold: disqualification.threshold,
+        }
+    }
+
+    pub(crate) fn disqualification(&self) -> Result<Disqualification, String> {
+        Ok(Disqualification {
+            rule: Rule::from_java_ordinal(self.rule).ok_or(format!("Unknown rule {}", self.rule))?,
+            measured: self.measured,
+            threshold: self.threshold,
+            gestures: u16::try_from(self.gestures).map_err(|_| format!("Invalid gesture bits {}", self.gestures))?,
+        })
+    }
+}
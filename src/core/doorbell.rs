@@ -0,0 +1,103 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::AtomicI64;
+use std::time::Duration;
+use nix::libc;
+use crate::core::futex;
+
+/// How one side of the service wakes the other after writing to the shared segment. The data
+/// itself always lives in the segment; a doorbell only says "look again", so waiters re-check the
+/// word they are waiting on and spurious or coalesced rings are harmless.
+#[derive(Debug)]
+pub(crate) enum Doorbell {
+    /// Futex on the word that was written. Needs nothing besides the shared mapping.
+    Futex,
+    /// `wait` reads from `incoming` and `ring` writes to `outgoing`; the other side holds the same
+    /// two eventfds the other way round.
+    EventFd { incoming: OwnedFd, outgoing: OwnedFd },
+    /// One end of a connected datagram socket pair. Each ring is a one byte datagram.
+    UnixSocket(UnixDatagram),
+}
+
+impl Doorbell {
+    /// Create a connected pair of eventfd doorbells, as `(server, client)`.
+    pub(crate) fn event_fd_pair() -> io::Result<(Doorbell, Doorbell)> {
+        let to_server = Self::event_fd()?;
+        let to_client = Self::event_fd()?;
+        let server = Doorbell::EventFd { incoming: to_server.try_clone()?, outgoing: to_client.try_clone()? };
+        let client = Doorbell::EventFd { incoming: to_client, outgoing: to_server };
+        Ok((server, client))
+    }
+
+    /// Create a connected pair of Unix datagram socket doorbells, as `(server, client)`.
+    pub(crate) fn unix_socket_pair() -> io::Result<(Doorbell, Doorbell)> {
+        let (server, client) = UnixDatagram::pair()?;
+        server.set_nonblocking(true)?;
+        client.set_nonblocking(true)?;
+        Ok((Doorbell::UnixSocket(server), Doorbell::UnixSocket(client)))
+    }
+
+    /// Wrap eventfds that were passed to this process at startup. Takes ownership of both.
+    pub(crate) unsafe fn event_fd_from_raw(incoming: RawFd, outgoing: RawFd) -> Doorbell {
+        Doorbell::EventFd { incoming: OwnedFd::from_raw_fd(incoming), outgoing: OwnedFd::from_raw_fd(outgoing) }
+    }
+
+    /// Wrap a datagram socket that was passed to this process at startup. Takes ownership of it.
+    pub(crate) unsafe fn unix_socket_from_raw(fd: RawFd) -> io::Result<Doorbell> {
+        let socket = UnixDatagram::from_raw_fd(fd);
+        socket.set_nonblocking(true)?;
+        Ok(Doorbell::UnixSocket(socket))
+    }
+
+    /// Wake the other side after storing a new value in `word`.
+    pub(crate) fn ring(&self, word: &AtomicI64) {
+        match self {
+            Doorbell::Futex => futex::wake(word),
+            Doorbell::EventFd { outgoing, .. } => {
+                let increment = 1u64;
+                // A full counter still means "rung", so a failed write can be ignored
+                unsafe { libc::write(outgoing.as_raw_fd(), &increment as *const u64 as *const libc::c_void, 8) };
+            }
+            Doorbell::UnixSocket(socket) => {
+                // A full socket buffer already holds a pending ring
+                let _ = socket.send(&[1]);
+            }
+        }
+    }
+
+    /// Block until the other side rings, as long as `word` still holds `expected`.
+    pub(crate) fn wait(&self, word: &AtomicI64, expected: i64, timeout: Option<Duration>) {
+        match self {
+            Doorbell::Futex => futex::wait(word, expected, timeout),
+            Doorbell::EventFd { incoming, .. } => {
+                if Self::poll_readable(incoming.as_raw_fd(), timeout) {
+                    // Reading resets the counter, consuming every ring so far
+                    let mut counter = 0u64;
+                    unsafe { libc::read(incoming.as_raw_fd(), &mut counter as *mut u64 as *mut libc::c_void, 8) };
+                }
+            }
+            Doorbell::UnixSocket(socket) => {
+                if Self::poll_readable(socket.as_raw_fd(), timeout) {
+                    let mut datagram = [0u8; 1];
+                    while socket.recv(&mut datagram).is_ok() {}
+                }
+            }
+        }
+    }
+
+    fn event_fd() -> io::Result<OwnedFd> {
+        match unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) } {
+            -1 => Err(io::Error::last_os_error()),
+            fd => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+        }
+    }
+
+    /// Wait until `fd` is readable. Returns false on timeout or error.
+    fn poll_readable(fd: RawFd, timeout: Option<Duration>) -> bool {
+        let mut poll_fd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        // Round up so that a short remaining timeout does not turn into a busy loop
+        let timeout_millis = timeout.map_or(-1, |timeout| timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32);
+        unsafe { libc::poll(&mut poll_fd, 1, timeout_millis) > 0 }
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::atomic::AtomicI64;
+use std::time::Duration;
+
+/// The futex syscall works on 32-bit words, so wait on the half of the i64 that holds its low
+/// bits. Every state and request ordinal fits in those bits.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn futex_word(word: &AtomicI64) -> *const u32 {
+    let address = word.as_ptr() as *const u32;
+    if cfg!(target_endian = "big") { address.wrapping_add(1) } else { address }
+}
+
+/// Block until `word` is woken up, as long as it still holds `expected`. Returns immediately if the
+/// value has already changed. Spurious wake-ups are possible, so callers re-check the word.
+/// Uses the shared (not process private) futex, so it works across processes mapping the segment.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn wait(word: &AtomicI64, expected: i64, timeout: Option<Duration>) {
+    use nix::libc;
+
+    let timespec = timeout.map(|timeout| libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    });
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            futex_word(word),
+            libc::FUTEX_WAIT,
+            expected as u32,
+            timespec.as_ref().map_or(std::ptr::null(), |timespec| timespec as *const libc::timespec),
+        );
+    }
+}
+
+/// Wake every process and thread blocked in `wait` on `word`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn wake(word: &AtomicI64) {
+    use nix::libc;
+
+    unsafe {
+        libc::syscall(libc::SYS_futex, futex_word(word), libc::FUTEX_WAKE, i32::MAX);
+    }
+}
+
+/// Without futexes, fall back to a short sleep so callers can keep their re-check loop.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn wait(_word: &AtomicI64, _expected: i64, timeout: Option<Duration>) {
+    let poll_interval = Duration::from_micros(100);
+    std::thread::sleep(timeout.map_or(poll_interval, |timeout| timeout.min(poll_interval)));
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn wake(_word: &AtomicI64) {}
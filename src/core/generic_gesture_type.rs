@@ -6,6 +6,12 @@ pub enum GenericGestureType {
     Swipe,
     Circle,
     Boomerang,
+    Pinch,
+    Spread,
+    Rotate,
+    MultiSwipe,
+    /// Held in place for at least the hold threshold, then dragged in a direction.
+    HoldDrag,
 }
 
 impl GenericGestureType {
@@ -16,11 +22,16 @@ impl GenericGestureType {
             GenericGestureType::Swipe => 2,
             GenericGestureType::Circle => 3,
             GenericGestureType::Boomerang => 4,
+            GenericGestureType::Pinch => 5,
+            GenericGestureType::Spread => 6,
+            GenericGestureType::Rotate => 7,
+            GenericGestureType::MultiSwipe => 8,
+            GenericGestureType::HoldDrag => 9,
         }
     }
 }
 
-pub(crate) const ALL_GESTURE_TYPES_POSSIBLE: u8 = 31u8;
+pub(crate) const ALL_GESTURE_TYPES_POSSIBLE: u16 = 1023u16;
 
 impl GenericGestureType {
     pub(crate) fn name(self) -> String {
@@ -31,9 +42,17 @@ impl GenericGestureType {
                 GenericGestureType::Swipe => "SWIPE",
                 GenericGestureType::Circle => "CIRCLE",
                 GenericGestureType::Boomerang => "BOOMERANG",
+                GenericGestureType::Pinch => "PINCH",
+                GenericGestureType::Spread => "SPREAD",
+                GenericGestureType::Rotate => "ROTATE",
+                GenericGestureType::MultiSwipe => "MULTI_SWIPE",
+                GenericGestureType::HoldDrag => "HOLD_DRAG",
             }
         )
     }
+    pub(crate) fn from_ordinal(ordinal: i64) -> Option<Self> {
+        Self::values().into_iter().find(|gesture| gesture.ordinal() == ordinal)
+    }
     pub(crate) fn values() -> Vec<Self> {
         vec![
             Self::Click,
@@ -41,24 +60,39 @@ impl GenericGestureType {
             Self::Swipe,
             Self::Circle,
             Self::Boomerang,
+            Self::Pinch,
+            Self::Spread,
+            Self::Rotate,
+            Self::MultiSwipe,
+            Self::HoldDrag,
         ]
     }
-    pub(crate) fn java_ordinal(&self) -> u8 {
+    pub(crate) fn java_ordinal(&self) -> u16 {
         match self {
-            GenericGestureType::Click => 1u8,
-            GenericGestureType::Hold => 2u8,
-            GenericGestureType::Swipe => 4u8,
-            GenericGestureType::Circle => 8u8,
-            GenericGestureType::Boomerang => 16u8,
+            GenericGestureType::Click => 1u16,
+            GenericGestureType::Hold => 2u16,
+            GenericGestureType::Swipe => 4u16,
+            GenericGestureType::Circle => 8u16,
+            GenericGestureType::Boomerang => 16u16,
+            GenericGestureType::Pinch => 32u16,
+            GenericGestureType::Spread => 64u16,
+            GenericGestureType::Rotate => 128u16,
+            GenericGestureType::MultiSwipe => 256u16,
+            GenericGestureType::HoldDrag => 512u16,
         }
     }
-    pub(crate) fn from_int_value(value: u8) -> Option<GenericGestureType> {
+    pub(crate) fn from_int_value(value: u16) -> Option<GenericGestureType> {
         match value {
             1 => Some(GenericGestureType::Click),
             2 => Some(GenericGestureType::Hold),
             4 => Some(GenericGestureType::Swipe),
             8 => Some(GenericGestureType::Circle),
             16 => Some(GenericGestureType::Boomerang),
+            32 => Some(GenericGestureType::Pinch),
+            64 => Some(GenericGestureType::Spread),
+            128 => Some(GenericGestureType::Rotate),
+            256 => Some(GenericGestureType::MultiSwipe),
+            512 => Some(GenericGestureType::HoldDrag),
             _ => None
         }
     }
@@ -69,17 +103,12 @@ impl GenericGestureType {
             "Swipe"|"SWIPE" => Some(GenericGestureType::Swipe),
             "Circle"|"CIRCLE" => Some(GenericGestureType::Circle),
             "Boomerang"|"BOOMERANG" => Some(GenericGestureType::Boomerang),
+            "Pinch"|"PINCH" => Some(GenericGestureType::Pinch),
+            "Spread"|"SPREAD" => Some(GenericGestureType::Spread),
+            "Rotate"|"ROTATE" => Some(GenericGestureType::Rotate),
+            "MultiSwipe"|"MULTI_SWIPE" => Some(GenericGestureType::MultiSwipe),
+            "HoldDrag"|"HOLD_DRAG" => Some(GenericGestureType::HoldDrag),
             _ => None,
         }
     }
-    pub(crate)fn from_non_generic_gesture_int_value(value: u8) -> Option<Self> {
-        match value {
-            1 => Some(GenericGestureType::Click),
-            2 => Some(GenericGestureType::Hold),
-            4..11 => Some(GenericGestureType::Swipe),
-            8..15 => Some(GenericGestureType::Boomerang),
-            16..18 => Some(GenericGestureType::Circle),
-            _ => None
-        }
-    }
 }
\ No newline at end of file
@@ -1,7 +1,23 @@
 use crate::core::direction::Direction;
 use crate::core::direction_result::DirectionResult;
-use crate::core::generic_gesture_type::GenericGestureType;
+use crate::core::direction_sectors::DirectionSectors;
+use crate::core::display::Display;
+use crate::core::disqualification::{Disqualification, Rule};
+use crate::core::gesture_detector::GestureDetector;
+use crate::core::generic_gesture_type::{GenericGestureType, ALL_GESTURE_TYPES_POSSIBLE};
+use crate::core::detect_gesture_multi_touch::pointer_tracks;
 use crate::core::point::Point;
+use crate::core::thresholds::Thresholds;
+use std::collections::BTreeMap;
+
+/// Number of consecutive provisional classifications that have to agree on a result before it
+/// is reported with full confidence.
+const PROVISIONAL_STABLE_POINTS: u32 = 8;
+
+/// A gesture in progress is classified again after every point until it has this many. After that
+/// it is classified again once it has grown by another 1/`PROVISIONAL_EVERY_POINT_UP_TO` of its
+/// points, so that streaming stays linear in the number of points instead of quadratic.
+const PROVISIONAL_EVERY_POINT_UP_TO: usize = 32;
 
 /// Struct for encapsulating gesture detection requests and responses.
 #[derive(Debug, Clone)]
@@ -9,8 +25,29 @@ pub struct GestureDetection {
     pub(crate) duration: i128,
     pub(crate) touch_points: Vec<Point>,
     pub(crate) direction_result: DirectionResult,
-    pub(crate) possible_gestures: u8,  // Bitfield to represent the 5 possible generic gesture types being qualified/disqualified
+    /// Heading of a directional result in degrees, anticlockwise from East, before it is
+    /// bucketed into the `Direction` of `direction_result`. None for results without a heading.
+    pub(crate) direction_angle: Option<f64>,
+    pub(crate) possible_gestures: u16,  // Bitfield to represent the 10 possible generic gesture types being qualified/disqualified
     pub(crate) fling_direction: Option<Direction>,
+    /// How headings are bucketed into directions.
+    pub(crate) direction_sectors: DirectionSectors,
+    /// What the rules compare durations and distances with.
+    pub(crate) thresholds: Thresholds,
+    /// The display `touch_points` are on, which they are converted from to dp for the detector.
+    pub(crate) display: Display,
+    /// While the touch is in progress, the result the gesture would have if it ended now.
+    pub(crate) provisional: Option<(GenericGestureType, DirectionResult)>,
+    /// Number of consecutive classifications for which `provisional` has not changed.
+    provisional_streak: u32,
+    /// Number of points at which a gesture in progress is next classified again.
+    next_provisional_at: usize,
+    /// The points of `touch_points` converted to dp so far, and the display they were converted
+    /// for, so that a streamed point is only converted once.
+    dp_points: Vec<Point>,
+    dp_display: Display,
+    /// Every rule applied through `remove` so far, in order.
+    pub(crate) disqualifications: Vec<Disqualification>,
 }
 
 impl GestureDetection {
@@ -20,27 +57,174 @@ impl GestureDetection {
         fling_direction: Option<Direction>,
     ) -> Self {
         // Initialize possible gestures with all bits set to 1
-        let possible_gestures = (1 << 4) - 1; // 4 gestures for 4 bits
+        let possible_gestures = ALL_GESTURE_TYPES_POSSIBLE; // 10 gestures for 10 bits
         Self {
             duration,
             touch_points,
             direction_result: DirectionResult::None,
+            direction_angle: None,
             possible_gestures,
             fling_direction,
+            direction_sectors: DirectionSectors::default(),
+            thresholds: Thresholds::default(),
+            display: Display::default(),
+            provisional: None,
+            provisional_streak: 0,
+            next_provisional_at: 0,
+            dp_points: Vec::new(),
+            dp_display: Display::default(),
+            disqualifications: Vec::new(),
         }
     }
 
-    /// Remove gestures by clearing corresponding bits
+    /// Add a point to a gesture that is still in progress and re-evaluate `possible_gestures`
+    /// with `detector`, as if the finger had been lifted at this point. Long gestures are only
+    /// re-evaluated every few points (see `PROVISIONAL_EVERY_POINT_UP_TO`), keeping the
+    /// provisional result in between. Returns the provisional gesture and its direction.
+    ///
+    /// The duration is taken from the timestamps. Points without timestamps leave it at 0, so a
+    /// gesture streamed without them is never provisionally a Hold (or a hold-then-drag) until
+    /// DETECT brings its duration.
+    pub(crate) fn add_point(&mut self, point: Point, detector: &dyn GestureDetector) -> (GenericGestureType, Option<DirectionResult>) {
+        self.touch_points.push(point);
+        if let Some(elapsed) = self.touch_points.first().and_then(|first| Some(point.timestamp? - first.timestamp?)) {
+            self.duration = elapsed as i128;
+        }
+        let count = self.touch_points.len();
+        if let Some((generic_gesture_type, direction_result)) = self.provisional.filter(|_| count < self.next_provisional_at) {
+            return (generic_gesture_type, Some(direction_result).filter(|&direction_result| direction_result != DirectionResult::None));
+        }
+        self.next_provisional_at = count + (count / PROVISIONAL_EVERY_POINT_UP_TO).max(1);
+        self.possible_gestures = ALL_GESTURE_TYPES_POSSIBLE;
+        self.direction_result = DirectionResult::None;
+        self.direction_angle = None;
+        self.disqualifications.clear();
+
+        let (generic_gesture_type, maybe_direction) = self.detect(detector);
+        let provisional = (generic_gesture_type, maybe_direction.unwrap_or(DirectionResult::None));
+        self.provisional_streak = if self.provisional == Some(provisional) { self.provisional_streak + 1 } else { 1 };
+        self.provisional = Some(provisional);
+        (generic_gesture_type, maybe_direction)
+    }
+
+    /// Classify the touch points with `detector`, converted to dp of `display` so that they can be
+    /// compared with the thresholds.
+    pub(crate) fn detect(&mut self, detector: &dyn GestureDetector) -> (GenericGestureType, Option<DirectionResult>) {
+        if self.dp_display != self.display || self.dp_points.len() > self.touch_points.len() {
+            self.dp_points.clear();
+            self.dp_display = self.display;
+        }
+        let display = self.display;
+        let converted = self.dp_points.len();
+        self.dp_points.extend(self.touch_points[converted..].iter().map(|&point| display.to_dp(point)));
+        detector.detect(&mut self.dp_points.clone(), self)
+    }
+
+    /// How sure the provisional result is, from 0 to 1. It grows with every point that leaves the
+    /// result unchanged, so a gesture that has looked the same for a while is reported as certain.
+    pub(crate) fn provisional_confidence(&self) -> f64 {
+        (self.provisional_streak as f64 / PROVISIONAL_STABLE_POINTS as f64).min(1.0)
+    }
+
+    /// The direction of the provisional result, if it had exactly one.
+    pub(crate) fn provisional_direction(&self) -> Option<Direction> {
+        match self.provisional {
+            Some((_, DirectionResult::Drag(direction) | DirectionResult::MultiDrag(direction, _))) => Some(direction),
+            _ => None,
+        }
+    }
+
+    /// Remove gestures by clearing corresponding bits, recording that `rule` ruled them out by
+    /// comparing `measured` with `threshold`.
     pub(crate) fn remove(&mut self,
-                         reason: &str,
-                         gestures: u8,
+                         gestures: u16,
+                         rule: Rule,
+                         measured: f64,
+                         threshold: f64,
     ) {
-        // print the reason
-        println!("{}", reason);
         self.possible_gestures &= !gestures;
+        self.disqualifications.push(Disqualification { rule, measured, threshold, gestures });
+    }
+
+    /// How well the rules applied so far support `gesture`, from 0 to 1: the average `evidence`
+    /// of the rules that concern it, so that rules passed narrowly lower the score. A gesture that
+    /// was ruled out scores no more than the evidence of the rule that ruled it out most clearly,
+    /// so that it never scores above a gesture that is still possible. 1 if no rule concerns it.
+    pub(crate) fn score(&self, gesture: GenericGestureType) -> f64 {
+        let evidence = self
+            .disqualifications
+            .iter()
+            .filter_map(|disqualification| disqualification.evidence(gesture))
+            .collect::<Vec<_>>();
+        if evidence.is_empty() {
+            return 1.0;
+        }
+        let average = evidence.iter().sum::<f64>() / evidence.len() as f64;
+        let strongest_strike = self
+            .disqualifications
+            .iter()
+            .filter(|disqualification| disqualification.gestures & gesture.java_ordinal() != 0)
+            .filter_map(|disqualification| disqualification.evidence(gesture))
+            .reduce(f64::min);
+        strongest_strike.map_or(average, |strike| average.min(strike))
+    }
+
+    /// Every gesture type with its `score`, best first. Gestures with the same score are ranked
+    /// the way `result` picks among the possible ones. The gestures that are still possible come
+    /// first, but the first candidate is not necessarily the result: that is the most specific
+    /// gesture possible, which may have passed its rules more narrowly than another.
+    pub(crate) fn candidates(&self) -> Vec<(GenericGestureType, f64)> {
+        let mut candidates = GenericGestureType::values()
+            .into_iter()
+            .map(|gesture| (gesture, self.score(gesture)))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(b.ordinal().cmp(&a.ordinal())));
+        candidates
+    }
+
+    /// Check whether a gesture has not been disqualified yet
+    pub(crate) fn is_possible(&self, gesture: GenericGestureType) -> bool {
+        self.possible_gestures & gesture.java_ordinal() != 0
+    }
+
+    /// The touch points of each finger, keyed by pointer id.
+    pub(crate) fn tracks(&self) -> BTreeMap<u32, Vec<Point>> {
+        pointer_tracks(&self.touch_points)
+    }
+
+    /// Velocity in pixels per millisecond when reaching the touch point at `index`, from the
+    /// previous point (or the next one, for the first point). None without usable timestamps.
+    pub(crate) fn velocity_at(&self, index: usize) -> Option<Point> {
+        let (from, to) = match index {
+            0 => (self.touch_points.first()?, self.touch_points.get(1)?),
+            _ => (self.touch_points.get(index - 1)?, self.touch_points.get(index)?),
+        };
+        Self::velocity_between(from, to)
+    }
+
+    /// Velocity in pixels per millisecond from the first to the last touch point.
+    pub(crate) fn average_velocity(&self) -> Option<Point> {
+        Self::velocity_between(self.touch_points.first()?, self.touch_points.last()?)
+    }
+
+    /// Distance travelled along the path per millisecond, between the first and the last touch
+    /// point. Unlike the length of `average_velocity`, this does not shrink when the path turns.
+    pub(crate) fn average_speed(&self) -> Option<f64> {
+        let elapsed = Self::elapsed_between(self.touch_points.first()?, self.touch_points.last()?)?;
+        Some(Point::path_length(&self.touch_points) / elapsed)
+    }
+
+    fn velocity_between(from: &Point, to: &Point) -> Option<Point> {
+        let elapsed = Self::elapsed_between(from, to)?;
+        Some(to.sub(*from).mul(1.0 / elapsed))
+    }
+
+    /// Milliseconds between two samples, if both are timestamped and in order.
+    fn elapsed_between(from: &Point, to: &Point) -> Option<f64> {
+        Some(to.timestamp? - from.timestamp?).filter(|&elapsed| elapsed > 0.0)
     }
 
-    fn result(&self) -> (GenericGestureType, &DirectionResult) {
+    pub(crate) fn result(&self) -> (GenericGestureType, &DirectionResult) {
         // Find the gesture type with the highest bit set in possible_gestures
         let mut detected_gesture = GenericGestureType::Click; // Default to Click if no gesture is detected
         let mut highest_bit = 0;
@@ -0,0 +1,107 @@
+use crate::core::detect_gesture_dummy::determine_gesture_dummy;
+use crate::core::detect_gesture_rule_based::determine_gesture_rule_based;
+use crate::core::direction_result::DirectionResult;
+use crate::core::generic_gesture_type::GenericGestureType;
+use crate::core::gesture_detection::GestureDetection;
+use crate::core::point::Point;
+use crate::core::template_recognizer::{Template, TemplateRecognizer};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Id of the `RuleBasedDetector` every server registers.
+pub(crate) const RULE_BASED_DETECTOR: u32 = 0;
+/// Id of the `TemplateDetector` every server registers, and the one it starts out using.
+pub(crate) const TEMPLATE_BASED_DETECTOR: u32 = 1;
+/// Id of the `DummyDetector` every server registers.
+pub(crate) const DUMMY_DETECTOR: u32 = 2;
+
+/// A gesture detection strategy. The server registers a few of them and classifies with the one
+/// the client selected through CONFIGURE, both finished and streamed gestures.
+pub(crate) trait GestureDetector: fmt::Debug + Send + Sync {
+    fn name(&self) -> String;
+
+    /// Classify the gesture in `touch_points`, ruling gestures out of `possible_gestures` and
+    /// storing the `DirectionResult` on the detection, like `determine_gesture_rule_based`.
+    fn detect(
+        &self,
+        touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>);
+
+    /// The registered template a finished single-finger stroke matches, with its score from 0 to
+    /// 1. Detectors that do not know about templates never match one.
+    fn recognize(&self, _stroke: &[Point]) -> Option<(Template, f64)> {
+        None
+    }
+}
+
+/// The rules of `determine_gesture_rule_based`, without templates.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RuleBasedDetector;
+
+impl GestureDetector for RuleBasedDetector {
+    fn name(&self) -> String {
+        String::from("RULE_BASED")
+    }
+
+    fn detect(
+        &self,
+        touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>) {
+        determine_gesture_rule_based(touch_points, gesture_detection)
+    }
+}
+
+/// The rules of `RuleBasedDetector`, with strokes that match one of the templates registered on
+/// the server reported as that template.
+#[derive(Debug, Clone)]
+pub(crate) struct TemplateDetector {
+    template_recognizer: Arc<Mutex<TemplateRecognizer>>,
+}
+
+impl TemplateDetector {
+    pub(crate) fn new(template_recognizer: Arc<Mutex<TemplateRecognizer>>) -> Self {
+        TemplateDetector { template_recognizer }
+    }
+}
+
+impl GestureDetector for TemplateDetector {
+    fn name(&self) -> String {
+        String::from("TEMPLATE_BASED")
+    }
+
+    fn detect(
+        &self,
+        touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>) {
+        RuleBasedDetector.detect(touch_points, gesture_detection)
+    }
+
+    fn recognize(&self, stroke: &[Point]) -> Option<(Template, f64)> {
+        self.template_recognizer
+            .lock()
+            .unwrap()
+            .recognize(stroke)
+            .map(|(template, score)| (template.clone(), score))
+    }
+}
+
+/// Reports every gesture as a click, for testing clients without depending on the rules.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DummyDetector;
+
+impl GestureDetector for DummyDetector {
+    fn name(&self) -> String {
+        String::from("DUMMY")
+    }
+
+    fn detect(
+        &self,
+        touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>) {
+        determine_gesture_dummy(touch_points, gesture_detection)
+    }
+}
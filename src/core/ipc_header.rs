@@ -0,0 +1,90 @@
+use std::fmt;
+
+/// First four bytes of every segment: "GEST" when read as a little-endian u32.
+pub(crate) const MAGIC: u32 = 0x5453_4547;
+
+/// Bumped whenever the layout or the meaning of any field in the segment changes.
+pub(crate) const PROTOCOL_VERSION: u32 = 20;
+
+/// Self-describing header at offset 0 of the shared memory segment. It is written once by
+/// whoever creates the segment, before the other side attaches, and checked by the side that
+/// attaches so that a mismatched client or server is rejected instead of reading garbage.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GestureDetectionIPCHeader {
+    pub(crate) magic: u32, // offset=0, +4
+    pub(crate) protocol_version: u32, // offset=4, +4
+    /// Size of everything before the point array, i.e. the byte offset of the first point.
+    pub(crate) header_length: u32, // offset=8, +4
+    /// Size of the whole segment in bytes.
+    pub(crate) segment_length: u32, // offset=12, +4
+    /// Number of points that fit in the point array.
+    pub(crate) point_capacity: u32, // offset=16, +4
+    reserved: u32, // offset=20, +4
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum HeaderError {
+    BadMagic { found: u32 },
+    UnsupportedVersion { found: u32 },
+    HeaderLengthMismatch { expected: u32, found: u32 },
+    SegmentTooSmall { segment_length: u32, mapped_length: usize },
+    PointCapacityTooLarge { point_capacity: u32, available: u32 },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::BadMagic { found } => {
+                write!(f, "not a gesture detection segment (magic {:#010x}, expected {:#010x})", found, MAGIC)
+            }
+            HeaderError::UnsupportedVersion { found } => {
+                write!(f, "protocol version {} is not supported (expected {})", found, PROTOCOL_VERSION)
+            }
+            HeaderError::HeaderLengthMismatch { expected, found } => {
+                write!(f, "header length is {} bytes, expected {}", found, expected)
+            }
+            HeaderError::SegmentTooSmall { segment_length, mapped_length } => {
+                write!(f, "segment claims {} bytes but only {} are mapped", segment_length, mapped_length)
+            }
+            HeaderError::PointCapacityTooLarge { point_capacity, available } => {
+                write!(f, "point capacity {} does not fit in the segment (room for {})", point_capacity, available)
+            }
+        }
+    }
+}
+
+impl GestureDetectionIPCHeader {
+    pub(crate) fn new(header_length: u32, segment_length: u32, point_capacity: u32) -> Self {
+        GestureDetectionIPCHeader {
+            magic: MAGIC,
+            protocol_version: PROTOCOL_VERSION,
+            header_length,
+            segment_length,
+            point_capacity,
+            reserved: 0,
+        }
+    }
+
+    /// Check a header written by the other side against this build's `header_length`, the number
+    /// of bytes actually mapped, and the size of one point in the point array.
+    pub(crate) fn validate(&self, header_length: u32, mapped_length: usize, point_size: u32) -> Result<(), HeaderError> {
+        if self.magic != MAGIC {
+            return Err(HeaderError::BadMagic { found: self.magic });
+        }
+        if self.protocol_version != PROTOCOL_VERSION {
+            return Err(HeaderError::UnsupportedVersion { found: self.protocol_version });
+        }
+        if self.header_length != header_length {
+            return Err(HeaderError::HeaderLengthMismatch { expected: header_length, found: self.header_length });
+        }
+        if self.segment_length < self.header_length || self.segment_length as usize > mapped_length {
+            return Err(HeaderError::SegmentTooSmall { segment_length: self.segment_length, mapped_length });
+        }
+        let available = (self.segment_length - self.header_length) / point_size;
+        if self.point_capacity > available {
+            return Err(HeaderError::PointCapacityTooLarge { point_capacity: self.point_capacity, available });
+        }
+        Ok(())
+    }
+}
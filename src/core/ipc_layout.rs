@@ -0,0 +1,101 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Package of the generated Java constants class, matching the JNI entry points in `libs.rs`.
+const JAVA_PACKAGE: &str = "com.example.myapp";
+const JAVA_CLASS: &str = "GestureDetectionLayout";
+const JSON_FILE: &str = "gesture_detection_layout.json";
+
+/// One fixed field of the shared memory segment, as seen by a client in another language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LayoutField {
+    pub(crate) name: &'static str,
+    pub(crate) offset: usize,
+    pub(crate) size: usize,
+    pub(crate) rust_type: &'static str,
+}
+
+/// Everything a client needs to lay out the segment: the fixed fields, followed by the point
+/// array, plus the protocol constants (ordinals and bit offsets) written into those fields.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Layout {
+    pub(crate) protocol_version: u32,
+    pub(crate) magic: u32,
+    pub(crate) segment_length: usize,
+    pub(crate) header_length: usize,
+    pub(crate) point_size: usize,
+    pub(crate) point_capacity: usize,
+    pub(crate) fields: Vec<LayoutField>,
+    pub(crate) constants: Vec<(&'static str, i64)>,
+}
+
+impl Layout {
+    /// Machine-readable description of the layout.
+    pub(crate) fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+        json += &format!("  \"protocol_version\": {},\n", self.protocol_version);
+        json += &format!("  \"magic\": {},\n", self.magic);
+        json += &format!("  \"segment_length\": {},\n", self.segment_length);
+        json += &format!("  \"header_length\": {},\n", self.header_length);
+        json += &format!("  \"point_size\": {},\n", self.point_size);
+        json += &format!("  \"point_capacity\": {},\n", self.point_capacity);
+        json += "  \"fields\": [\n";
+        let fields = self.fields
+            .iter()
+            .map(|field| format!(
+                "    {{ \"name\": \"{}\", \"offset\": {}, \"size\": {}, \"type\": \"{}\" }}",
+                field.name, field.offset, field.size, field.rust_type
+            ))
+            .collect::<Vec<_>>();
+        json += &fields.join(",\n");
+        json += "\n  ],\n  \"constants\": {\n";
+        let constants = self.constants
+            .iter()
+            .map(|(name, value)| format!("    \"{}\": {}", name, value))
+            .collect::<Vec<_>>();
+        json += &constants.join(",\n");
+        json += "\n  }\n}\n";
+        json
+    }
+
+    /// Java constants class for the Android client (also usable from Kotlin).
+    pub(crate) fn to_java(&self) -> String {
+        let mut java = String::from("// Generated by `cargo run -- --generate-layout`. Do not edit.\n");
+        java += &format!("package {};\n\npublic final class {} {{\n", JAVA_PACKAGE, JAVA_CLASS);
+        java += &format!("    private {}() {{}}\n\n", JAVA_CLASS);
+        java += &format!("    public static final int PROTOCOL_VERSION = {};\n", self.protocol_version);
+        java += &format!("    public static final int MAGIC = {:#010x};\n", self.magic);
+        java += &format!("    public static final int SEGMENT_LENGTH = {};\n", self.segment_length);
+        java += &format!("    public static final int HEADER_LENGTH = {};\n", self.header_length);
+        java += &format!("    public static final int POINT_SIZE = {};\n", self.point_size);
+        java += &format!("    public static final int POINT_CAPACITY = {};\n\n", self.point_capacity);
+        for field in &self.fields {
+            let constant = field.name.to_uppercase();
+            java += &format!("    public static final int {}_OFFSET = {};\n", constant, field.offset);
+            java += &format!("    public static final int {}_SIZE = {};\n", constant, field.size);
+        }
+        java += "\n";
+        for (name, value) in &self.constants {
+            java += &format!("    public static final long {} = {}L;\n", name, value);
+        }
+        java += "}\n";
+        java
+    }
+
+    /// Write the JSON description and the Java class into `directory`.
+    pub(crate) fn write_files(&self, directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        fs::write(directory.join(JSON_FILE), self.to_json())?;
+        fs::write(directory.join(format!("{}.java", JAVA_CLASS)), self.to_java())
+    }
+
+    /// Compare against files previously written by `write_files`, returning the stale file names.
+    pub(crate) fn stale_files(&self, directory: &Path) -> Vec<String> {
+        [(String::from(JSON_FILE), self.to_json()), (format!("{}.java", JAVA_CLASS), self.to_java())]
+            .into_iter()
+            .filter(|(file, contents)| fs::read_to_string(directory.join(file)).ok().as_ref() != Some(contents))
+            .map(|(file, _)| file)
+            .collect()
+    }
+}
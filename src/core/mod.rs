@@ -1,15 +1,33 @@
 pub(crate) mod client_command;
+pub(crate) mod compass_point;
+pub(crate) mod configuration;
 pub(crate) mod detect_gesture_dummy;
+pub(crate) mod detect_gesture_multi_touch;
+pub(crate) mod detect_gesture_rule_based;
 pub(crate) mod detector_state;
 pub(crate) mod direction;
 pub(crate) mod direction_result;
+pub(crate) mod direction_sectors;
+pub(crate) mod display;
+pub(crate) mod disqualification;
+pub(crate) mod doorbell;
+pub(crate) mod futex;
 pub(crate) mod generic_gesture_type;
 pub(crate) mod gesture_detection;
+pub(crate) mod gesture_detector;
+pub(crate) mod ipc_header;
+pub(crate) mod ipc_layout;
 pub(crate) mod point;
+pub(crate) mod result_record;
 pub(crate) mod rotation_direction;
 pub(crate) mod server_state;
+pub(crate) mod shared_memory;
+pub(crate) mod tap_sequencer;
+pub(crate) mod template_recognizer;
+pub(crate) mod thresholds;
 pub(crate) mod detection_system;
 pub(crate) mod test_strings;
+#[cfg(test)]
 pub(crate) mod tests;
 mod libs;
 
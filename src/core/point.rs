@@ -1,14 +1,44 @@
-/// Represents a point in 2D space.
+/// Represents a point in 2D space, optionally sampled from a touch at a known time and pressure.
 #[derive(Debug, Copy, Clone)]
 pub struct Point {
     pub(crate) x: f64,
     pub(crate) y: f64,
+    /// Milliseconds on the client's clock, if the touch sample was timestamped. The clock keeps
+    /// running between gestures, so that taps can be timed by it as well.
+    pub(crate) timestamp: Option<f64>,
+    /// Normalized touch pressure, usually between 0 and 1, if the device reports it.
+    pub(crate) pressure: Option<f64>,
+    /// Which finger the sample belongs to, as reported by Android's `MotionEvent.getPointerId`.
+    /// Single-pointer gestures use pointer 0 throughout.
+    pub(crate) pointer_id: u32,
 }
 
 impl Point {
     // Constructor
     pub(crate) fn new(x: f64, y: f64) -> Point {
-        Point { x, y }
+        Point { x, y, timestamp: None, pressure: None, pointer_id: 0 }
+    }
+
+    // Constructor for a touch sample
+    pub(crate) fn sampled(x: f64, y: f64, timestamp: Option<f64>, pressure: Option<f64>) -> Point {
+        Point { x, y, timestamp, pressure, pointer_id: 0 }
+    }
+
+    // The same sample, attributed to another finger
+    pub(crate) fn on_pointer(self, pointer_id: u32) -> Point {
+        Point { pointer_id, ..self }
+    }
+
+    // The x, y, timestamp, pressure and pointer id as stored in shared memory, with NaN for a
+    // missing value
+    pub(crate) fn to_fields(self) -> [f64; 5] {
+        [self.x, self.y, self.timestamp.unwrap_or(f64::NAN), self.pressure.unwrap_or(f64::NAN), self.pointer_id as f64]
+    }
+
+    // Inverse of `to_fields`
+    pub(crate) fn from_fields(fields: [f64; 5]) -> Point {
+        let optional = |value: f64| if value.is_nan() { None } else { Some(value) };
+        Point::sampled(fields[0], fields[1], optional(fields[2]), optional(fields[3])).on_pointer(fields[4] as u32)
     }
 
     // Subtract two points
@@ -26,14 +56,38 @@ impl Point {
         Point::new(self.x * scalar, self.y * scalar)
     }
 
+    // Euclidean distance between two points
+    pub(crate) fn distance_to(&self, other: &Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
     pub(crate) fn rotate(&self, origin: &Point, degrees: f64) -> Point {
         let radians = degrees.to_radians();
         let x = origin.x + (self.x - origin.x) * radians.cos() - (self.y - origin.y) * radians.sin();
         let y = origin.y + (self.x - origin.x) * radians.sin() + (self.y - origin.y) * radians.cos();
-        Point::new(x, y)
+        Point { x, y, ..*self }
+    }
+
+    // Average position of `points`, with NaN coordinates if there are none
+    pub(crate) fn centroid(points: &[Point]) -> Point {
+        points.iter().fold(Point::new(0.0, 0.0), |sum, point| sum.add(*point)).mul(1.0 / points.len() as f64)
+    }
+
+    // Average distance of `points` from `center`, 0 if there are none
+    pub(crate) fn mean_distance(points: &[Point], center: &Point) -> f64 {
+        if points.is_empty() {
+            return 0.0;
+        }
+        points.iter().map(|point| point.distance_to(center)).sum::<f64>() / points.len() as f64
+    }
+
+    // Length of the path through `points`, in order
+    pub(crate) fn path_length(points: &[Point]) -> f64 {
+        points.windows(2).map(|pair| pair[0].distance_to(&pair[1])).sum()
     }
 }
 
+/// Points compare and hash by position only, so the same path sampled at different times is equal.
 impl PartialEq for Point {
     fn eq(&self, other: &Self) -> bool {
         self.x == other.x && self.y == other.y
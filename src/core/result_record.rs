@@ -0,0 +1,275 @@
+use crate::core::detect_gesture_multi_touch::pointer_tracks;
+use crate::core::detection_system::NO_TEMPLATE;
+use crate::core::direction::Direction;
+use crate::core::direction_result::DirectionResult;
+use crate::core::generic_gesture_type::GenericGestureType;
+use crate::core::point::Point;
+use crate::core::rotation_direction::RotationDirection;
+
+/// Stored in the ordinal fields of a `ResultRecord` that have no value.
+pub(crate) const NO_ORDINAL: i64 = -1;
+
+/// Number of ranked candidates published with each result.
+pub(crate) const CANDIDATE_COUNT: usize = 4;
+
+/// Why a gesture could not be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DetectionError {
+    /// The point array size or start did not fit in the segment.
+    InvalidPointArray,
+    /// A stream ended with fewer or more points than its DETECT command announced.
+    MissingPoints,
+}
+
+impl DetectionError {
+    pub(crate) fn name(self) -> String {
+        String::from(
+            match self {
+                DetectionError::InvalidPointArray => "INVALID_POINT_ARRAY",
+                DetectionError::MissingPoints => "MISSING_POINTS",
+            }
+        )
+    }
+
+    /// The code stored in `error_code`. 0 means there was no error.
+    pub(crate) fn java_ordinal(self) -> i64 {
+        match self {
+            DetectionError::InvalidPointArray => 1,
+            DetectionError::MissingPoints => 2,
+        }
+    }
+
+    pub(crate) fn from_java_ordinal(ordinal: i64) -> Option<Self> {
+        match ordinal {
+            1 => Some(DetectionError::InvalidPointArray),
+            2 => Some(DetectionError::MissingPoints),
+            _ => None,
+        }
+    }
+}
+
+/// Everything the server reports about a detected gesture, for clients that would rather not
+/// take the `result` word apart bit by bit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct GestureResult {
+    /// None if the gesture could not be detected, see `error`.
+    pub(crate) gesture: Option<GenericGestureType>,
+    pub(crate) direction_result: DirectionResult,
+    /// Number of taps in the sequence a click belongs to, and 0 for every other gesture.
+    pub(crate) taps: u32,
+    /// How sure the detector is, from 0 to 1.
+    pub(crate) confidence: f64,
+    pub(crate) error: Option<DetectionError>,
+    /// Smallest and largest coordinates of the touch points, None without any points.
+    pub(crate) bounding_box: Option<(Point, Point)>,
+    pub(crate) centroid: Option<Point>,
+    /// Distance travelled by all fingers together, in pixels.
+    pub(crate) path_length: f64,
+    /// Id of the template the stroke matched, if any.
+    pub(crate) template_id: Option<u32>,
+}
+
+impl GestureResult {
+    /// A detected gesture, with the geometry of its touch points.
+    pub(crate) fn detected(
+        gesture: GenericGestureType,
+        direction_result: DirectionResult,
+        confidence: f64,
+        touch_points: &[Point],
+    ) -> Self {
+        GestureResult { gesture: Some(gesture), direction_result, confidence, ..Self::measure(touch_points) }
+    }
+
+    /// A gesture that could not be detected. The geometry is filled in from whatever points
+    /// did arrive.
+    pub(crate) fn failed(error: DetectionError, touch_points: &[Point]) -> Self {
+        GestureResult { error: Some(error), ..Self::measure(touch_points) }
+    }
+
+    /// No gesture and no error, just the geometry of the touch points.
+    pub(crate) fn measure(touch_points: &[Point]) -> Self {
+        let bounding_box = touch_points.first().map(|first| {
+            touch_points.iter().fold((*first, *first), |(min, max), point| {
+                (Point::new(min.x.min(point.x), min.y.min(point.y)), Point::new(max.x.max(point.x), max.y.max(point.y)))
+            })
+        });
+        let centroid = (!touch_points.is_empty()).then(|| Point::centroid(touch_points));
+        let path_length = pointer_tracks(touch_points).values().map(|track| Point::path_length(track)).sum();
+        GestureResult {
+            gesture: None,
+            direction_result: DirectionResult::None,
+            taps: 0,
+            confidence: 0.0,
+            error: None,
+            bounding_box,
+            centroid,
+            path_length,
+            template_id: None,
+        }
+    }
+}
+
+/// `GestureResult` as stored in the segment: ordinals are -1 when absent, and coordinates NaN.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ResultRecord {
+    /// `GenericGestureType` ordinal (Click = 0 ... HoldDrag = 9).
+    pub(crate) gesture_type: i64, // offset=0, +8
+    /// `Direction` ordinal of a swipe, boomerang, hold-then-drag or multi-finger swipe.
+    pub(crate) direction: i64, // offset=8, +8
+    /// The other `Direction` ordinal of an ambiguous heading.
+    pub(crate) alternative_direction: i64, // offset=16, +8
+    /// `RotationDirection` ordinal of a circle or a two-finger rotation.
+    pub(crate) rotation: i64, // offset=24, +8
+    /// Scale factor of a pinch or spread, degrees of a rotation, or fingers of a multi-finger swipe.
+    pub(crate) measurement: f64, // offset=32, +8
+    pub(crate) taps: i64, // offset=40, +8
+    pub(crate) confidence: f64, // offset=48, +8
+    /// `DetectionError` ordinal, or 0.
+    pub(crate) error_code: i64, // offset=56, +8
+    pub(crate) min_x: f64, // offset=64, +8
+    pub(crate) min_y: f64, // offset=72, +8
+    pub(crate) max_x: f64, // offset=80, +8
+    pub(crate) max_y: f64, // offset=88, +8
+    pub(crate) centroid_x: f64, // offset=96, +8
+    pub(crate) centroid_y: f64, // offset=104, +8
+    pub(crate) path_length: f64, // offset=112, +8
+    /// Id of the template the stroke matched, or `NO_TEMPLATE`.
+    pub(crate) template_id: i64, // offset=120, +8
+}
+
+impl ResultRecord {
+    /// Written by the server when a detection completes.
+    pub(crate) fn encode(gesture_result: &GestureResult) -> Self {
+        let (direction, alternative_direction, rotation, measurement) = match gesture_result.direction_result {
+            DirectionResult::Drag(direction) => (direction.java_ordinal() as i64, NO_ORDINAL, NO_ORDINAL, 0.0),
+            DirectionResult::AmbiguousDrag(nearest, other) => {
+                (nearest.java_ordinal() as i64, other.java_ordinal() as i64, NO_ORDINAL, 0.0)
+            }
+            DirectionResult::MultiDrag(direction, fingers) => {
+                (direction.java_ordinal() as i64, NO_ORDINAL, NO_ORDINAL, fingers as f64)
+            }
+            DirectionResult::Circular(rotation) => (NO_ORDINAL, NO_ORDINAL, rotation.java_ordinal() as i64, 0.0),
+            DirectionResult::Rotation(rotation, angle) => (NO_ORDINAL, NO_ORDINAL, rotation.java_ordinal() as i64, angle),
+            DirectionResult::Scale(scale) => (NO_ORDINAL, NO_ORDINAL, NO_ORDINAL, scale),
+            DirectionResult::None => (NO_ORDINAL, NO_ORDINAL, NO_ORDINAL, 0.0),
+        };
+        let (min, max) = gesture_result.bounding_box.unwrap_or((Point::new(f64::NAN, f64::NAN), Point::new(f64::NAN, f64::NAN)));
+        let centroid = gesture_result.centroid.unwrap_or(Point::new(f64::NAN, f64::NAN));
+        ResultRecord {
+            gesture_type: gesture_result.gesture.map_or(NO_ORDINAL, |gesture| gesture.ordinal()),
+            direction,
+            alternative_direction,
+            rotation,
+            measurement,
+            taps: gesture_result.taps as i64,
+            confidence: gesture_result.confidence,
+            error_code: gesture_result.error.map_or(0, DetectionError::java_ordinal),
+            min_x: min.x,
+            min_y: min.y,
+            max_x: max.x,
+            max_y: max.y,
+            centroid_x: centroid.x,
+            centroid_y: centroid.y,
+            path_length: gesture_result.path_length,
+            template_id: gesture_result.template_id.map_or(NO_TEMPLATE, i64::from),
+        }
+    }
+
+    /// Read by the client once the server has published the record. Fails on ordinals that do
+    /// not belong to the gesture type, e.g. a swipe without a direction.
+    pub(crate) fn decode(&self) -> Result<GestureResult, String> {
+        let gesture = match self.gesture_type {
+            NO_ORDINAL => None,
+            ordinal => Some(
+                GenericGestureType::from_ordinal(ordinal).ok_or(format!("Unknown gesture type {}", ordinal))?,
+            ),
+        };
+        let direction = || match self.direction {
+            ordinal @ 0..8 => Ok(Direction::from_index(ordinal as usize)),
+            ordinal => Err(format!("Invalid direction {}", ordinal)),
+        };
+        let rotation = || match self.rotation {
+            0 => Ok(RotationDirection::Clockwise),
+            1 => Ok(RotationDirection::AntiClockwise),
+            ordinal => Err(format!("Invalid rotation {}", ordinal)),
+        };
+        let direction_result = match gesture {
+            None | Some(GenericGestureType::Click | GenericGestureType::Hold) => DirectionResult::None,
+            Some(GenericGestureType::Swipe | GenericGestureType::Boomerang | GenericGestureType::HoldDrag) => {
+                match self.alternative_direction {
+                    NO_ORDINAL => DirectionResult::Drag(direction()?),
+                    ordinal @ 0..8 => DirectionResult::AmbiguousDrag(direction()?, Direction::from_index(ordinal as usize)),
+                    ordinal => return Err(format!("Invalid direction {}", ordinal)),
+                }
+            }
+            Some(GenericGestureType::MultiSwipe) => DirectionResult::MultiDrag(direction()?, self.measurement as u8),
+            Some(GenericGestureType::Circle) => DirectionResult::Circular(rotation()?),
+            Some(GenericGestureType::Rotate) => DirectionResult::Rotation(rotation()?, self.measurement),
+            Some(GenericGestureType::Pinch | GenericGestureType::Spread) => DirectionResult::Scale(self.measurement),
+        };
+        let error = match self.error_code {
+            0 => None,
+            code => Some(DetectionError::from_java_ordinal(code).ok_or(format!("Unknown error code {}", code))?),
+        };
+        let template_id = match self.template_id {
+            NO_TEMPLATE => None,
+            id => Some(u32::try_from(id).map_err(|_| format!("Invalid template id {}", id))?),
+        };
+        let point = |x: f64, y: f64| (!x.is_nan() && !y.is_nan()).then(|| Point::new(x, y));
+        Ok(GestureResult {
+            gesture,
+            direction_result,
+            taps: u32::try_from(self.taps).map_err(|_| format!("Invalid tap count {}", self.taps))?,
+            confidence: self.confidence,
+            error,
+            bounding_box: point(self.min_x, self.min_y).zip(point(self.max_x, self.max_y)),
+            centroid: point(self.centroid_x, self.centroid_y),
+            path_length: self.path_length,
+            template_id,
+        })
+    }
+}
+
+impl Default for ResultRecord {
+    /// The record before the first detection: no gesture and no error.
+    fn default() -> Self {
+        Self::encode(&GestureResult::measure(&[]))
+    }
+}
+
+/// One of the gesture types a detection could have been, with its score from 0 to 1. Unused
+/// entries have a `gesture_type` of `NO_ORDINAL` and a score of 0.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CandidateEntry {
+    /// `GenericGestureType` ordinal.
+    pub(crate) gesture_type: i64, // offset=0, +8
+    pub(crate) score: f64, // offset=8, +8
+}
+
+impl CandidateEntry {
+    pub(crate) const EMPTY: CandidateEntry = CandidateEntry { gesture_type: NO_ORDINAL, score: 0.0 };
+}
+
+/// The first `CANDIDATE_COUNT` of `candidates`, best first, padded with empty entries.
+pub(crate) fn encode_candidates(candidates: &[(GenericGestureType, f64)]) -> [CandidateEntry; CANDIDATE_COUNT] {
+    let mut entries = [CandidateEntry::EMPTY; CANDIDATE_COUNT];
+    for (entry, (gesture, score)) in entries.iter_mut().zip(candidates) {
+        *entry = CandidateEntry { gesture_type: gesture.ordinal(), score: *score };
+    }
+    entries
+}
+
+/// The candidates stored by `encode_candidates`, without the empty entries.
+pub(crate) fn decode_candidates(entries: &[CandidateEntry; CANDIDATE_COUNT]) -> Result<Vec<(GenericGestureType, f64)>, String> {
+    entries
+        .iter()
+        .take_while(|entry| entry.gesture_type != NO_ORDINAL)
+        .map(|entry| {
+            GenericGestureType::from_ordinal(entry.gesture_type)
+                .map(|gesture| (gesture, entry.score))
+                .ok_or(format!("Unknown gesture type {}", entry.gesture_type))
+        })
+        .collect()
+}
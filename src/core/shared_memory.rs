@@ -0,0 +1,113 @@
+use std::ffi::c_void;
+use std::num::NonZeroUsize;
+use std::ops::Deref;
+use std::os::fd::{AsFd, AsRawFd};
+use std::ptr::{self, NonNull};
+use nix::errno::Errno;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::sys::stat::fstat;
+#[cfg(not(target_os = "android"))]
+use nix::{fcntl::OFlag, sys::mman::{shm_open, shm_unlink}, sys::stat::Mode, unistd::ftruncate};
+use crate::core::detection_system::GestureDetectionIPCBuffer;
+
+/// Name of the POSIX shared memory object used by the service unless another one is requested.
+pub(crate) const SHARED_MEMORY_NAME: &str = "/gesture_detection_service";
+
+/// Size of the shared memory segment in bytes: one page, which is what the mapping takes anyway.
+/// The fixed fields alone take 448 bytes, so the 1 KB the segment used to have would leave room
+/// for only 14 points.
+pub(crate) const SHARED_MEMORY_SIZE: usize = 4096;
+
+/// A shared memory segment mapped into this process. The `GestureDetectionIPCBuffer` lives at the
+/// start of the mapping and is read and written in place, so every process mapping the same
+/// segment sees the same state.
+#[derive(Debug)]
+pub(crate) struct SharedMemorySegment {
+    address: NonNull<c_void>,
+    length: usize,
+    /// False when the mapping was handed to us (e.g. over JNI) and must not be unmapped here.
+    mapped: bool,
+    /// Set when this process created the named object and should unlink it when done.
+    owned_name: Option<String>,
+}
+
+// The segment is plain memory shared with other processes; access to it is coordinated by the
+// protocol in GESTURE_DETECTION_SERVICE.md rather than by Rust ownership.
+unsafe impl Send for SharedMemorySegment {}
+unsafe impl Sync for SharedMemorySegment {}
+
+impl SharedMemorySegment {
+    /// Used by the server to create (or truncate) the named segment and initialize the buffer.
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn create(name: &str) -> nix::Result<Self> {
+        let fd = shm_open(name, OFlag::O_CREAT | OFlag::O_RDWR, Mode::S_IRUSR | Mode::S_IWUSR)?;
+        ftruncate(&fd, SHARED_MEMORY_SIZE as i64)?;
+        let mut segment = Self::from_fd(&fd)?;
+        segment.owned_name = Some(String::from(name));
+        unsafe { ptr::write(segment.address.as_ptr() as *mut GestureDetectionIPCBuffer, GestureDetectionIPCBuffer::new()) };
+        Ok(segment)
+    }
+
+    /// Used by a client to attach to a segment that the server already created.
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn open(name: &str) -> nix::Result<Self> {
+        let fd = shm_open(name, OFlag::O_RDWR, Mode::empty())?;
+        Self::from_fd(&fd)
+    }
+
+    /// Map a shared memory file descriptor, e.g. one handed over from an Android `SharedMemory`.
+    pub(crate) fn from_fd<Fd: AsFd>(fd: Fd) -> nix::Result<Self> {
+        let length = fstat(fd.as_fd().as_raw_fd())?.st_size as usize;
+        if length < size_of::<GestureDetectionIPCBuffer>() {
+            return Err(Errno::EINVAL);
+        }
+        let address = unsafe {
+            mmap(
+                None,
+                NonZeroUsize::new(length).ok_or(Errno::EINVAL)?,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                fd,
+                0,
+            )?
+        };
+        Ok(SharedMemorySegment { address, length, mapped: true, owned_name: None })
+    }
+
+    /// When a client has provided the starting address of an already mapped segment, operate on
+    /// it in place. The caller keeps ownership of the mapping.
+    pub(crate) unsafe fn from_raw(address: *mut c_void, length: usize) -> Option<Self> {
+        if length < size_of::<GestureDetectionIPCBuffer>() {
+            return None;
+        }
+        NonNull::new(address).map(|address| SharedMemorySegment { address, length, mapped: false, owned_name: None })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.address.as_ptr() as *mut u8
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.length
+    }
+}
+
+impl Deref for SharedMemorySegment {
+    type Target = GestureDetectionIPCBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*(self.address.as_ptr() as *const GestureDetectionIPCBuffer) }
+    }
+}
+
+impl Drop for SharedMemorySegment {
+    fn drop(&mut self) {
+        if self.mapped {
+            let _ = unsafe { munmap(self.address, self.length) };
+        }
+        #[cfg(not(target_os = "android"))]
+        if let Some(name) = self.owned_name.take() {
+            let _ = shm_unlink(name.as_str());
+        }
+    }
+}
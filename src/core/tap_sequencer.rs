@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+use crate::core::generic_gesture_type::GenericGestureType;
+use crate::core::point::Point;
+
+const DEFAULT_MAXIMUM_TAP_INTERVAL: Duration = Duration::from_millis(300);
+const DEFAULT_MAXIMUM_TAP_DISTANCE: f64 = 50f64;
+
+/// How close in time and space successive clicks have to be to count as one multi-tap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TapThresholds {
+    /// Longest time between the end of one tap and the end of the next. Zero turns taps off.
+    pub(crate) maximum_interval: Duration,
+    /// Furthest a tap may land from the first tap of its sequence, in dp (see `Display`).
+    pub(crate) maximum_distance: f64,
+}
+
+impl Default for TapThresholds {
+    fn default() -> Self {
+        TapThresholds {
+            maximum_interval: DEFAULT_MAXIMUM_TAP_INTERVAL,
+            maximum_distance: DEFAULT_MAXIMUM_TAP_DISTANCE,
+        }
+    }
+}
+
+impl TapThresholds {
+    /// Fails if either threshold is negative, or the distance is not finite.
+    pub(crate) fn new(maximum_interval: i64, maximum_distance: f64) -> Result<Self, String> {
+        let maximum_interval = u64::try_from(maximum_interval)
+            .map_err(|_| format!("Tap interval {}ms is negative", maximum_interval))?;
+        if !(maximum_distance >= 0.0 && maximum_distance.is_finite()) {
+            return Err(format!("Tap distance {} is not a number of dp", maximum_distance));
+        }
+        Ok(TapThresholds { maximum_interval: Duration::from_millis(maximum_interval), maximum_distance })
+    }
+}
+
+/// When a tap ended: when the server classified it, and when its last sample was taken on the
+/// client's clock, if the samples were timestamped.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TapTime {
+    pub(crate) classified: Instant,
+    pub(crate) sampled: Option<f64>,
+}
+
+impl TapTime {
+    /// Time since an earlier tap ended. The timestamps are used when both taps have them, so that
+    /// time spent waiting for the server does not count.
+    fn since(&self, earlier: &TapTime) -> Duration {
+        match (self.sampled, earlier.sampled) {
+            (Some(sampled), Some(earlier_sampled)) => {
+                Duration::try_from_secs_f64((sampled - earlier_sampled).max(0.0) / 1000.0).unwrap_or(Duration::MAX)
+            }
+            _ => self.classified.saturating_duration_since(earlier.classified),
+        }
+    }
+}
+
+/// Groups successive Click detections into taps of a sequence. Every detection goes through
+/// `register`, so that any other gesture in between ends the sequence.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TapSequencer {
+    /// Where the sequence started, and when its last tap ended.
+    sequence: Option<(Point, TapTime)>,
+    count: u32,
+}
+
+impl TapSequencer {
+    pub(crate) fn reset(&mut self) {
+        self.sequence = None;
+        self.count = 0;
+    }
+
+    /// Record a detected gesture that started at `position` and ended at `ended`. Returns the
+    /// number of taps in the sequence so far if it was a click, and 0 otherwise.
+    pub(crate) fn register(
+        &mut self,
+        thresholds: &TapThresholds,
+        generic_gesture_type: GenericGestureType,
+        position: Point,
+        ended: TapTime,
+    ) -> u32 {
+        if generic_gesture_type != GenericGestureType::Click {
+            self.reset();
+            return 0;
+        }
+        match self.sequence {
+            Some((start, last_tap))
+                if !thresholds.maximum_interval.is_zero()
+                    && ended.since(&last_tap) <= thresholds.maximum_interval
+                    && position.distance_to(&start) <= thresholds.maximum_distance =>
+            {
+                self.sequence = Some((start, ended));
+                self.count += 1;
+            }
+            _ => {
+                self.sequence = Some((position, ended));
+                self.count = 1;
+            }
+        }
+        self.count
+    }
+}
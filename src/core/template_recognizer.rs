@@ -0,0 +1,278 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::core::point::Point;
+
+/// Number of points every stroke is resampled to before it is compared.
+const RESAMPLED_POINTS: usize = 64;
+/// Side of the square strokes are scaled into, in pixels.
+const SQUARE_SIZE: f64 = 250f64;
+/// How far (in degrees) either side of the indicative angle to search for the best alignment.
+const ANGLE_RANGE: f64 = 45f64;
+/// The search stops once the remaining range is smaller than this many degrees.
+const ANGLE_PRECISION: f64 = 2f64;
+/// Strokes scoring lower than this against every template are not recognized.
+pub(crate) const MINIMUM_TEMPLATE_SCORE: f64 = 0.8;
+/// Longest template name in bytes of UTF-8, as stored in the shared memory. Kept short so that
+/// the field does not cost the point array a point.
+pub(crate) const TEMPLATE_NAME_LENGTH: usize = 16;
+
+/// Starts the line of the registry file that holds the id the next template will get.
+const NEXT_ID_KEY: &str = "next_id";
+
+/// Half the diagonal of the square, the largest average distance two normalized strokes can have.
+fn half_diagonal() -> f64 {
+    0.5 * (2.0 * SQUARE_SIZE * SQUARE_SIZE).sqrt()
+}
+
+/// A named stroke, stored normalized so that it can be compared directly.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template {
+    pub(crate) id: u32,
+    pub(crate) name: String,
+    pub(crate) points: Vec<Point>,
+}
+
+/// One template in the list the server writes into the point array for LIST_TEMPLATES: its id
+/// and its name in UTF-8, padded with zeros.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TemplateEntry {
+    pub(crate) id: i64, // offset=0, +8
+    pub(crate) name: [u8; TEMPLATE_NAME_LENGTH], // offset=8, +16
+}
+
+impl TemplateEntry {
+    pub(crate) fn new(template: &Template) -> Self {
+        TemplateEntry { id: template.id as i64, name: encode_name(&template.name).unwrap_or_default() }
+    }
+
+    pub(crate) fn name(&self) -> Result<String, String> {
+        decode_name(&self.name)
+    }
+}
+
+/// A template name padded with zeros to `TEMPLATE_NAME_LENGTH` bytes. Names have to fit, and may
+/// not be empty or contain control characters, since the registry file is line based.
+pub(crate) fn encode_name(name: &str) -> Result<[u8; TEMPLATE_NAME_LENGTH], String> {
+    if name.is_empty() || name.len() > TEMPLATE_NAME_LENGTH || name.chars().any(char::is_control) {
+        return Err(format!("Invalid template name {:?}: it must be 1 to {} bytes without control characters", name, TEMPLATE_NAME_LENGTH));
+    }
+    let mut encoded = [0u8; TEMPLATE_NAME_LENGTH];
+    encoded[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(encoded)
+}
+
+/// Inverse of `encode_name`.
+pub(crate) fn decode_name(encoded: &[u8; TEMPLATE_NAME_LENGTH]) -> Result<String, String> {
+    let length = encoded.iter().position(|&byte| byte == 0).unwrap_or(TEMPLATE_NAME_LENGTH);
+    let name = String::from_utf8(encoded[..length].to_vec()).map_err(|error| error.to_string())?;
+    encode_name(&name)?;
+    Ok(name)
+}
+
+/// Unistroke recognizer in the style of the $1 recognizer (Wobbrock, Wilson and Li, 2007).
+/// Strokes are resampled to equally spaced points, rotated so that the line from their centroid
+/// to their first point is horizontal, scaled into a square and moved to the origin, then matched
+/// against every template by their average point distance at the best rotation.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TemplateRecognizer {
+    templates: Vec<Template>,
+    /// The id the next template gets. Saved along with the templates, so that removing the
+    /// newest one does not make its id available again after a restart.
+    next_id: u32,
+}
+
+impl TemplateRecognizer {
+    /// Register a stroke under `name`, returning the id that `recognize` reports for it. Strokes
+    /// without any length cannot be told apart and are rejected.
+    pub(crate) fn add_template(&mut self, name: &str, stroke: &[Point]) -> Result<u32, String> {
+        encode_name(name)?;
+        let points = normalize(stroke).ok_or_else(|| format!("Template {} has no length", name))?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.templates.push(Template { id, name: String::from(name), points });
+        Ok(id)
+    }
+
+    /// Forget a template. Returns false if there was none with this id. Ids are not reused.
+    pub(crate) fn remove_template(&mut self, id: u32) -> bool {
+        let count = self.templates.len();
+        self.templates.retain(|template| template.id != id);
+        self.templates.len() != count
+    }
+
+    pub(crate) fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+
+    /// Read templates written by `save`. A missing file is an empty registry. Files without a
+    /// `next_id` line continue after the highest id in them.
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(format!("Failed to read {}: {}", path.display(), error)),
+        };
+        let mut recognizer = Self::default();
+        for (index, line) in text.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+            if let Some(next_id) = line.strip_prefix(NEXT_ID_KEY).and_then(|rest| rest.strip_prefix('\t')) {
+                let next_id = next_id
+                    .parse::<u32>()
+                    .map_err(|error| format!("{}:{}: Invalid next id {:?}: {}", path.display(), index + 1, next_id, error))?;
+                recognizer.next_id = recognizer.next_id.max(next_id);
+                continue;
+            }
+            let template = parse_template(line).map_err(|error| format!("{}:{}: {}", path.display(), index + 1, error))?;
+            recognizer.next_id = recognizer.next_id.max(template.id + 1);
+            recognizer.templates.push(template);
+        }
+        Ok(recognizer)
+    }
+
+    /// Write the next id and then every template to `path`, one per line as its id, name and
+    /// normalized points separated by tabs. The file is replaced in one step, so a crash never
+    /// leaves half of it.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let templates = self.templates
+            .iter()
+            .map(|template| {
+                let points = template.points.iter().map(|point| format!("{},{}", point.x, point.y)).collect::<Vec<_>>();
+                format!("{}\t{}\t{}\n", template.id, template.name, points.join(" "))
+            })
+            .collect::<String>();
+        let text = format!("{}\t{}\n{}", NEXT_ID_KEY, self.next_id, templates);
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, path)
+    }
+
+    /// The template that matches `stroke` best and its score from 0 to 1, if it scores at least
+    /// `MINIMUM_TEMPLATE_SCORE`.
+    pub(crate) fn recognize(&self, stroke: &[Point]) -> Option<(&Template, f64)> {
+        let candidate = normalize(stroke)?;
+        self.templates
+            .iter()
+            .map(|template| {
+                let distance = distance_at_best_angle(&candidate, &template.points);
+                (template, 1.0 - distance / half_diagonal())
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .filter(|&(_, score)| score >= MINIMUM_TEMPLATE_SCORE)
+    }
+}
+
+/// One line of the registry file written by `save`.
+fn parse_template(line: &str) -> Result<Template, String> {
+    let mut fields = line.splitn(3, '\t');
+    let (Some(id), Some(name), Some(points)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err(String::from("Expected an id, a name and points separated by tabs"));
+    };
+    let id = id.parse::<u32>().map_err(|error| format!("Invalid id {:?}: {}", id, error))?;
+    encode_name(name)?;
+    let points = points
+        .split(' ')
+        .map(|point| {
+            let coordinates = point.split_once(',').and_then(|(x, y)| Some((x.parse::<f64>().ok()?, y.parse::<f64>().ok()?)));
+            coordinates.map(|(x, y)| Point::new(x, y)).ok_or_else(|| format!("Invalid point {:?}", point))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if points.len() != RESAMPLED_POINTS {
+        return Err(format!("Expected {} points but found {}", RESAMPLED_POINTS, points.len()));
+    }
+    Ok(Template { id, name: String::from(name), points })
+}
+
+/// Resample, rotate, scale and translate a stroke. None if the stroke has no length.
+fn normalize(stroke: &[Point]) -> Option<Vec<Point>> {
+    let points = resample(stroke)?;
+    let centroid = Point::centroid(&points);
+    let indicative_angle = (points[0].y - centroid.y).atan2(points[0].x - centroid.x).to_degrees();
+    let rotated = points.iter().map(|point| point.rotate(&centroid, -indicative_angle)).collect::<Vec<_>>();
+    Some(translate_to_origin(&scale_to_square(&rotated)))
+}
+
+/// `RESAMPLED_POINTS` points spaced evenly along the path.
+fn resample(stroke: &[Point]) -> Option<Vec<Point>> {
+    let length = Point::path_length(stroke);
+    if length <= 0.0 {
+        return None;
+    }
+    let interval = length / (RESAMPLED_POINTS - 1) as f64;
+    let mut resampled = vec![Point::new(stroke[0].x, stroke[0].y)];
+    let mut travelled = 0f64;
+    let mut previous = resampled[0];
+    for point in &stroke[1..] {
+        let mut segment = previous.distance_to(point);
+        while travelled + segment >= interval && resampled.len() < RESAMPLED_POINTS {
+            let next = previous.add(point.sub(previous).mul((interval - travelled) / segment));
+            resampled.push(next);
+            segment -= interval - travelled;
+            previous = next;
+            travelled = 0.0;
+        }
+        travelled += segment;
+        previous = Point::new(point.x, point.y);
+    }
+    // Rounding can leave the last point out
+    while resampled.len() < RESAMPLED_POINTS {
+        resampled.push(Point::new(stroke[stroke.len() - 1].x, stroke[stroke.len() - 1].y));
+    }
+    Some(resampled)
+}
+
+/// Scale both axes independently into a `SQUARE_SIZE` square. A nearly straight stroke has
+/// hardly any extent across it, so it is scaled uniformly instead.
+fn scale_to_square(points: &[Point]) -> Vec<Point> {
+    let (minimum_x, maximum_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), point| (lo.min(point.x), hi.max(point.x)));
+    let (minimum_y, maximum_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), point| (lo.min(point.y), hi.max(point.y)));
+    let extent = (maximum_x - minimum_x).max(maximum_y - minimum_y);
+    let width = if maximum_x - minimum_x > extent * 0.1 { maximum_x - minimum_x } else { extent };
+    let height = if maximum_y - minimum_y > extent * 0.1 { maximum_y - minimum_y } else { extent };
+    points
+        .iter()
+        .map(|point| Point::new(point.x * SQUARE_SIZE / width, point.y * SQUARE_SIZE / height))
+        .collect()
+}
+
+fn translate_to_origin(points: &[Point]) -> Vec<Point> {
+    let centroid = Point::centroid(points);
+    points.iter().map(|point| point.sub(centroid)).collect()
+}
+
+/// Average distance between corresponding points.
+fn path_distance(a: &[Point], b: &[Point]) -> f64 {
+    a.iter().zip(b).map(|(p, q)| p.distance_to(q)).sum::<f64>() / a.len() as f64
+}
+
+fn distance_at_angle(candidate: &[Point], template: &[Point], degrees: f64) -> f64 {
+    let origin = Point::centroid(candidate);
+    let rotated = candidate.iter().map(|point| point.rotate(&origin, degrees)).collect::<Vec<_>>();
+    path_distance(&rotated, template)
+}
+
+/// Golden section search for the rotation within `ANGLE_RANGE` that aligns the strokes best.
+fn distance_at_best_angle(candidate: &[Point], template: &[Point]) -> f64 {
+    let phi = 0.5 * (5f64.sqrt() - 1.0);
+    let (mut from, mut to) = (-ANGLE_RANGE, ANGLE_RANGE);
+    let mut x1 = phi * from + (1.0 - phi) * to;
+    let mut f1 = distance_at_angle(candidate, template, x1);
+    let mut x2 = (1.0 - phi) * from + phi * to;
+    let mut f2 = distance_at_angle(candidate, template, x2);
+    while (to - from).abs() > ANGLE_PRECISION {
+        if f1 < f2 {
+            to = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = phi * from + (1.0 - phi) * to;
+            f1 = distance_at_angle(candidate, template, x1);
+        } else {
+            from = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = (1.0 - phi) * from + phi * to;
+            f2 = distance_at_angle(candidate, template, x2);
+        }
+    }
+    f1.min(f2)
+}
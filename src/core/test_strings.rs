@@ -1,9 +1,118 @@
+use regex::{Captures, Regex};
+use crate::core::direction::Direction;
 use crate::core::direction_result::DirectionResult;
 use crate::core::generic_gesture_type::GenericGestureType;
 use crate::core::point::Point;
+use crate::core::rotation_direction::RotationDirection;
+
+/// Matches one labelled sample: a `:` separated list of `x,y` points, the duration on the next
+/// line and the expected gesture (optionally followed by its direction) on the line after that.
+const TEST_DATA_TEXT_PATTERN: &str = r"(?P<touch_points>-?\d+(\.\d+)?,-?\d+(\.\d+)?(:-?\d+(\.\d+)?,-?\d+(\.\d+)?)*)\r?\n(?P<duration>\d+)\r?\n(?P<gesture_type>\w+)( (?P<direction>\w+))?";
 
 pub(crate) enum TestStringPart {
     Answer{generic_gesture_type: GenericGestureType, direction_result: DirectionResult},
     TouchPointList{touch_points: Vec<Point>},
     Duration{duration: u64},
-}
\ No newline at end of file
+}
+
+impl TestStringPart {
+    fn touch_point_list(text: &str) -> Result<Self, String> {
+        let touch_points = text
+            .split(':')
+            .map(|pair| match pair.split_once(',') {
+                Some((x, y)) => Ok(Point::new(
+                    x.parse().map_err(|_| format!("Invalid x coordinate in '{}'", pair))?,
+                    y.parse().map_err(|_| format!("Invalid y coordinate in '{}'", pair))?,
+                )),
+                None => Err(format!("Invalid point '{}'", pair)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TestStringPart::TouchPointList { touch_points })
+    }
+
+    fn duration(text: &str) -> Result<Self, String> {
+        text.parse()
+            .map(|duration| TestStringPart::Duration { duration })
+            .map_err(|_| format!("Invalid duration '{}'", text))
+    }
+
+    fn answer(gesture_type: &str, direction: Option<&str>) -> Result<Self, String> {
+        let generic_gesture_type = GenericGestureType::value_of(gesture_type)
+            .ok_or(format!("Unknown gesture type '{}'", gesture_type))?;
+        let direction_result = match (generic_gesture_type, direction) {
+            (GenericGestureType::Click | GenericGestureType::Hold, None) => DirectionResult::None,
+            (GenericGestureType::Circle, Some(rotation)) => DirectionResult::Circular(
+                RotationDirection::value_of(rotation).ok_or(format!("Unknown rotation '{}'", rotation))?
+            ),
+            (GenericGestureType::Swipe | GenericGestureType::Boomerang, Some(direction)) => DirectionResult::Drag(
+                Direction::value_of(direction).ok_or(format!("Unknown direction '{}'", direction))?
+            ),
+            (_, direction) => return Err(format!("{} cannot have direction {:?}", gesture_type, direction)),
+        };
+        Ok(TestStringPart::Answer { generic_gesture_type, direction_result })
+    }
+}
+
+/// One labelled sample from `test_data.txt`.
+#[derive(Debug, Clone)]
+pub(crate) struct TestCase {
+    pub(crate) touch_points: Vec<Point>,
+    pub(crate) duration: u64,
+    pub(crate) generic_gesture_type: GenericGestureType,
+    pub(crate) direction_result: DirectionResult,
+}
+
+impl TestCase {
+    fn from_parts(parts: Vec<TestStringPart>) -> Result<Self, String> {
+        let (mut touch_points, mut duration, mut answer) = (None, None, None);
+        for part in parts {
+            match part {
+                TestStringPart::TouchPointList { touch_points: points } => touch_points = Some(points),
+                TestStringPart::Duration { duration: millis } => duration = Some(millis),
+                TestStringPart::Answer { generic_gesture_type, direction_result } => {
+                    answer = Some((generic_gesture_type, direction_result))
+                }
+            }
+        }
+        match (touch_points, duration, answer) {
+            (Some(touch_points), Some(duration), Some((generic_gesture_type, direction_result))) => Ok(TestCase {
+                touch_points,
+                duration,
+                generic_gesture_type,
+                direction_result,
+            }),
+            _ => Err(String::from("Incomplete test case")),
+        }
+    }
+
+    fn from_captures(captures: &Captures) -> Result<Self, String> {
+        Self::from_parts(vec![
+            TestStringPart::touch_point_list(&captures["touch_points"])?,
+            TestStringPart::duration(&captures["duration"])?,
+            TestStringPart::answer(
+                &captures["gesture_type"],
+                captures.name("direction").map(|direction| direction.as_str()),
+            )?,
+        ])
+    }
+}
+
+/// Parse the contents of `test_data.txt` into typed test cases. Every non-blank line has to belong
+/// to a sample, so a malformed entry is reported instead of silently skipped.
+pub(crate) fn parse_test_data(contents: &str) -> Result<Vec<TestCase>, String> {
+    let re = Regex::new(TEST_DATA_TEXT_PATTERN).unwrap();
+    let mut test_cases = Vec::new();
+    let mut last_end = 0;
+    for captures in re.captures_iter(contents) {
+        let whole = captures.get(0).unwrap();
+        if !contents[last_end..whole.start()].trim().is_empty() {
+            return Err(format!("Unparseable test data: '{}'", contents[last_end..whole.start()].trim()));
+        }
+        test_cases.push(TestCase::from_captures(&captures)?);
+        last_end = whole.end();
+    }
+    if !contents[last_end..].trim().is_empty() {
+        return Err(format!("Unparseable test data: '{}'", contents[last_end..].trim()));
+    }
+    Ok(test_cases)
+}
@@ -1,21 +1,1537 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::fmt;
 use std::fs::read_to_string;
-use regex::Regex;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::core::client_command::ClientCommand;
+use crate::core::compass_point::CompassPoint;
+use crate::core::configuration::{Configuration, ConfigurationBlock};
+use crate::core::detection_system::{CompositeGestureDetectionClient, GestureDetectionDaemon, GestureDetectionIPC, GestureDetectionIPCBuffer, GestureDetectionServer, COMMAND_RING_CAPACITY, ERROR_BITWISE_OFFSET, IS_ALIVE, POINT_ARRAY_SIZE};
+use crate::core::detect_gesture_rule_based::determine_gesture_rule_based;
+use crate::core::direction::{CompassMode, Direction};
+use crate::core::direction_result::DirectionResult;
+use crate::core::direction_sectors::{BoundaryPolicy, DirectionSectors};
+use crate::core::display::{Display, BASELINE_DENSITY};
+use crate::core::disqualification::{Disqualification, DisqualificationEntry, Rule};
+use crate::core::doorbell::Doorbell;
+use crate::core::generic_gesture_type::{GenericGestureType, ALL_GESTURE_TYPES_POSSIBLE};
+use crate::core::gesture_detection::GestureDetection;
+use crate::core::gesture_detector::{GestureDetector, RuleBasedDetector, DUMMY_DETECTOR, RULE_BASED_DETECTOR, TEMPLATE_BASED_DETECTOR};
+use crate::core::ipc_header::{GestureDetectionIPCHeader, HeaderError, PROTOCOL_VERSION};
+use crate::core::ipc_layout::Layout;
+use crate::core::point::Point;
+use crate::core::result_record::{decode_candidates, encode_candidates, CandidateEntry, DetectionError, GestureResult, ResultRecord, CANDIDATE_COUNT};
+use crate::core::rotation_direction::RotationDirection;
+use crate::core::shared_memory::{SharedMemorySegment, SHARED_MEMORY_SIZE};
+use crate::core::tap_sequencer::{TapSequencer, TapThresholds, TapTime};
+use crate::core::template_recognizer::TemplateRecognizer;
+use crate::core::thresholds::Thresholds;
+use crate::core::test_strings::{parse_test_data, TestCase};
 
-pub(crate) fn run_tests() {
-    // test_all_gestures_are_detected();
-    test_server_state_flow();
+/// Counts of expected (row) versus detected (column) gesture types, plus the number of samples
+/// whose gesture type was right but whose direction or rotation was not.
+#[derive(Debug, Default)]
+pub(crate) struct ConfusionMatrix {
+    counts: HashMap<(GenericGestureType, GenericGestureType), usize>,
+    wrong_directions: HashMap<GenericGestureType, usize>,
+    failures: Vec<String>,
 }
 
+impl ConfusionMatrix {
+    /// Run every test case through `detector` and tally the results.
+    pub(crate) fn evaluate(detector: &dyn GestureDetector, test_cases: &[TestCase]) -> Self {
+        let mut matrix = ConfusionMatrix::default();
+        for (index, test_case) in test_cases.iter().enumerate() {
+            let mut detection = GestureDetection::new(test_case.duration as i128, test_case.touch_points.clone(), None);
+            let (generic_gesture_type, maybe_direction) = detector.detect(&mut test_case.touch_points.clone(), &mut detection);
+            let direction_result = maybe_direction.unwrap_or(DirectionResult::None);
+            *matrix.counts.entry((test_case.generic_gesture_type, generic_gesture_type)).or_insert(0) += 1;
+
+            if generic_gesture_type != test_case.generic_gesture_type || direction_result != test_case.direction_result {
+                if generic_gesture_type == test_case.generic_gesture_type {
+                    *matrix.wrong_directions.entry(generic_gesture_type).or_insert(0) += 1;
+                }
+                matrix.failures.push(format!(
+                    "case {}: expected {} {:?}, detected {} {:?}",
+                    index,
+                    test_case.generic_gesture_type.name(),
+                    test_case.direction_result,
+                    generic_gesture_type.name(),
+                    direction_result,
+                ));
+            }
+        }
+        matrix
+    }
+
+    pub(crate) fn count(&self, expected: GenericGestureType, detected: GenericGestureType) -> usize {
+        *self.counts.get(&(expected, detected)).unwrap_or(&0)
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    pub(crate) fn correct(&self) -> usize {
+        self.total() - self.failures.len()
+    }
+
+    pub(crate) fn failures(&self) -> &[String] {
+        &self.failures
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>12}", "expected")?;
+        for detected in GenericGestureType::values() {
+            write!(f, "{:>11}", detected.name())?;
+        }
+        writeln!(f, "{:>11}", "WRONG_DIR")?;
+        for expected in GenericGestureType::values() {
+            write!(f, "{:>12}", expected.name())?;
+            for detected in GenericGestureType::values() {
+                write!(f, "{:>11}", self.count(expected, detected))?;
+            }
+            writeln!(f, "{:>11}", self.wrong_directions.get(&expected).unwrap_or(&0))?;
+        }
+        write!(f, "accuracy: {}/{}", self.correct(), self.total())
+    }
+}
+
+#[test]
 fn test_all_gestures_are_detected() {
-    let file_contents = read_to_string("src/core/test_data.txt").expect("Failed to read failed");
-    let test_data_text_pattern = r"(?P<first_point>\d+\.\d+,\d+\.\d+)(?P<other_points>:(\d+\.\d+,\d+\.\d+))*\n(?P<duration>\d+)\n(?P<gesture_type>\w+( (?P<direction>\w+))?)";
-    let re = Regex::new(test_data_text_pattern).unwrap();
-    let names = re.capture_names().into_iter()
-        .filter(|maybe_name| maybe_name.is_some())
-        .map(|maybe_name| maybe_name.unwrap())
-        .collect::<Vec<_>>();
-    println!("{:?}", re.is_match(&file_contents));
+    let file_contents = read_to_string("src/core/test_data.txt").expect("Failed to read test data");
+    let test_cases = parse_test_data(&file_contents).unwrap();
+    assert!(!test_cases.is_empty());
+
+    let matrix = ConfusionMatrix::evaluate(&RuleBasedDetector, &test_cases);
+    println!("{}", matrix);
+    assert!(matrix.failures().is_empty(), "misclassified samples:\n{}", matrix.failures().join("\n"));
+}
+
+#[test]
+fn test_parse_test_data() {
+    let test_cases = parse_test_data("1.5,2:3,4.25\r\n120\r\nSWIPE NORTHEAST\r\n\r\n5,6\n600\nHOLD\n").unwrap();
+    assert_eq!(test_cases.len(), 2);
+    assert_eq!(test_cases[0].touch_points, vec![Point::new(1.5, 2.0), Point::new(3.0, 4.25)]);
+    assert_eq!(test_cases[0].duration, 120);
+    assert_eq!(test_cases[0].generic_gesture_type, GenericGestureType::Swipe);
+    assert!(matches!(test_cases[0].direction_result, DirectionResult::Drag(Direction::Northeast)));
+    assert_eq!(test_cases[1].generic_gesture_type, GenericGestureType::Hold);
+    assert_eq!(test_cases[1].direction_result, DirectionResult::None);
+
+    assert!(parse_test_data("1,2\n100\nSPIN\n").is_err());
+    assert!(parse_test_data("1,2\n100\nCLICK\nnot a sample\n").is_err());
+}
+
+/// The default configuration, with every click reported as a click rather than as a tap of a
+/// sequence, for tests that send the same click over and over.
+fn without_taps() -> Configuration {
+    Configuration { tap_thresholds: TapThresholds { maximum_interval: Duration::ZERO, ..TapThresholds::default() }, ..Configuration::default() }
 }
 
+#[test]
 fn test_server_state_flow() {
-}
\ No newline at end of file
+    let name = format!("/gesture_detection_state_flow_test_{}", std::process::id());
+    let server = GestureDetectionServer::new(SharedMemorySegment::create(&name).unwrap());
+    server.set_configuration(without_taps()).unwrap();
+    let client = CompositeGestureDetectionClient::attach(&name);
+    let server_thread = thread::spawn(move || server.serve());
+
+    assert!(client.is_alive());
+    assert_eq!(client.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    // A second round-trip only works if the first one was acknowledged and cleared
+    assert_eq!(client.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+
+    client.halt();
+    server_thread.join().unwrap();
+    assert!(!client.is_alive());
+}
+
+#[test]
+fn test_event_fd_doorbell_round_trip() {
+    let (server_doorbell, client_doorbell) = Doorbell::event_fd_pair().unwrap();
+    let mut daemon = GestureDetectionDaemon::with_doorbells(
+        &format!("/gesture_detection_event_fd_test_{}", std::process::id()),
+        server_doorbell,
+        client_doorbell,
+    );
+    assert_eq!(daemon.configure(without_taps().to_block()), 0);
+    for _ in 0..10 {
+        assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    }
+    daemon.stop();
+}
+
+#[test]
+fn test_unix_socket_doorbell_round_trip() {
+    let (server_doorbell, client_doorbell) = Doorbell::unix_socket_pair().unwrap();
+    let mut daemon = GestureDetectionDaemon::with_doorbells(
+        &format!("/gesture_detection_unix_socket_test_{}", std::process::id()),
+        server_doorbell,
+        client_doorbell,
+    );
+    assert_eq!(daemon.configure(without_taps().to_block()), 0);
+    for _ in 0..10 {
+        assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    }
+    daemon.stop();
+}
+
+#[test]
+fn test_daemon_round_trips_are_fast() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_daemon_test_{}", std::process::id()));
+    assert_eq!(daemon.configure(without_taps().to_block()), 0);
+    let start_time = Instant::now();
+    for _ in 0..100 {
+        assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    }
+    // Sleep polling needed at least 100ms per round-trip
+    assert!(start_time.elapsed() < Duration::from_secs(2), "100 round-trips took {:?}", start_time.elapsed());
+    daemon.stop();
+}
+
+#[test]
+fn test_points_round_trip_through_the_daemon() {
+    let test_cases = parse_test_data(&read_to_string("src/core/test_data.txt").unwrap()).unwrap();
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_points_test_{}", std::process::id()));
+    for (index, test_case) in test_cases.iter().enumerate() {
+        let expected = GestureDetectionServer::encode_result(test_case.generic_gesture_type, Some(test_case.direction_result));
+        let result = daemon.detect(test_case.duration as i128, -1, test_case.touch_points.clone());
+        assert_eq!(result, expected, "case {}: {:?}", index, test_case);
+    }
+    daemon.stop();
+}
+
+#[test]
+fn test_point_array_is_bounds_checked() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_bounds_test_{}", std::process::id()));
+    let line = |count: usize| (0..count).map(|i| Point::new(10.0 * i as f64, 0.0)).collect::<Vec<_>>();
+
+    assert_eq!(daemon.detect(100, -1, line(POINT_ARRAY_SIZE + 1)), -1);
+    // A full point array still fits, and every point arrives: only the last one makes it long enough
+    let mut points = vec![Point::new(0.0, 0.0); POINT_ARRAY_SIZE - 1];
+    points.push(Point::new(500.0, 0.0));
+    let result = daemon.detect(100, -1, points);
+    assert_ne!(result, -1);
+    assert_ne!(result, GenericGestureType::Click.java_ordinal() as i64);
+    // Rejecting an oversized gesture leaves the server ready for the next one
+    assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    daemon.stop();
+}
+
+/// A client that only knows the published layout, like one written in another language: it finds
+/// every field and constant by name in `GestureDetectionIPCBuffer::layout()`, and plays its side
+/// of the protocol by hand on its own mapping of the segment, so it can write anything at all.
+struct LayoutClient {
+    segment: SharedMemorySegment,
+    layout: Layout,
+}
+
+impl LayoutClient {
+    fn attach(name: &str) -> Self {
+        LayoutClient { segment: SharedMemorySegment::open(name).unwrap(), layout: GestureDetectionIPCBuffer::layout() }
+    }
+
+    fn offset(&self, field: &str) -> usize {
+        self.layout.fields.iter().find(|layout_field| layout_field.name == field).unwrap_or_else(|| panic!("No field {}", field)).offset
+    }
+
+    fn constant(&self, constant: &str) -> i64 {
+        self.layout.constants.iter().find(|(name, _)| *name == constant).unwrap_or_else(|| panic!("No constant {}", constant)).1
+    }
+
+    fn word(&self, field: &str) -> &AtomicI64 {
+        self.word_at(self.offset(field))
+    }
+
+    fn word_at(&self, offset: usize) -> &AtomicI64 {
+        unsafe { &*(self.segment.as_ptr().add(offset) as *const AtomicI64) }
+    }
+
+    fn read(&self, field: &str) -> i64 {
+        self.word(field).load(Ordering::Acquire)
+    }
+
+    fn write(&self, field: &str, value: i64) {
+        self.word(field).store(value, Ordering::Relaxed)
+    }
+
+    /// Publish CLIENT_REQUEST_`request` and wait for SERVER_STATE_`state`.
+    fn request(&self, request: &str, state: &str) {
+        self.word("client_request").store(self.constant(&format!("CLIENT_REQUEST_{}", request)), Ordering::Release);
+        Doorbell::Futex.ring(self.word("client_request"));
+        let (server_state, expected) = (self.word("server_state"), self.constant(&format!("SERVER_STATE_{}", state)));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server_state.load(Ordering::Acquire) != expected {
+            assert!(Instant::now() < deadline, "{} was not acknowledged with {}", request, state);
+            Doorbell::Futex.wait(server_state, server_state.load(Ordering::Acquire), Some(Duration::from_millis(10)));
+        }
+    }
+
+    /// DETECT whatever `size` and `start` describe, returning the result and the error code.
+    fn detect_raw(&self, size: i64, start: i64) -> (i64, i64) {
+        self.request("BEGIN", "WAITING_FOR_ARGS");
+        self.write("duration", 100);
+        self.write("fling_direction", -1);
+        self.write("point_array_size", size);
+        self.write("point_array_start", start);
+        self.request("DETECT", "DETECTION_COMPLETE");
+        let outcome = (self.read("result"), self.read("result_error_code"));
+        self.request("CLEAR_ACKNOWLEDGEMENT", "WAITING_FOR_COMMAND");
+        outcome
+    }
+}
+
+#[test]
+fn test_server_rejects_point_arrays_written_out_of_bounds() {
+    let name = format!("/gesture_detection_raw_bounds_test_{}", std::process::id());
+    let mut daemon = GestureDetectionDaemon::with_name(&name);
+    let client = LayoutClient::attach(&name);
+    let error = 1 << client.constant("ERROR_BITWISE_OFFSET");
+    let invalid = (error, client.constant("ERROR_CODE_INVALID_POINT_ARRAY"));
+    let (header_length, point_size) = (client.layout.header_length as i64, client.layout.point_size as i64);
+    let capacity = client.layout.point_capacity as i64;
+
+    assert_eq!(client.detect_raw(capacity + 1, header_length), invalid);
+    assert_eq!(client.detect_raw(-1, header_length), invalid);
+    assert_eq!(client.detect_raw(i64::MAX, header_length), invalid);
+    // Starting inside the fixed fields, misaligned, or so late that the points run off the end
+    assert_eq!(client.detect_raw(1, 0), invalid);
+    assert_eq!(client.detect_raw(1, header_length + 1), invalid);
+    assert_eq!(client.detect_raw(2, SHARED_MEMORY_SIZE as i64 - point_size), invalid);
+    assert_eq!(client.detect_raw(1, i64::MAX - 7), invalid);
+    assert_eq!(daemon.result_record().unwrap().error, Some(DetectionError::InvalidPointArray));
+
+    // The last point that fits is still read, and the server carries on as before
+    assert_eq!(client.detect_raw(1, SHARED_MEMORY_SIZE as i64 - point_size).1, 0);
+    daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]);
+    assert_eq!(daemon.result_record().unwrap().gesture, Some(GenericGestureType::Click));
+    daemon.stop();
+}
+
+/// The integer constants of the checked in `GestureDetectionLayout.java`, which Java clients are
+/// built against, by name.
+fn java_layout_constants() -> HashMap<String, i64> {
+    read_to_string("layout/GestureDetectionLayout.java")
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("public static final ")?.split_once(" = "))
+        .filter_map(|(declaration, value)| {
+            let name = declaration.split_whitespace().last()?;
+            Some((String::from(name), value.trim_end_matches(';').trim_end_matches('L').parse::<i64>().ok()?))
+        })
+        .collect()
+}
+
+#[test]
+fn test_result_record_decodes_with_the_java_layout_constants() {
+    let name = format!("/gesture_detection_java_record_test_{}", std::process::id());
+    let mut daemon = GestureDetectionDaemon::with_name(&name);
+    let client = LayoutClient::attach(&name);
+    let java = java_layout_constants();
+    let constant = |name: &str| *java.get(name).unwrap_or_else(|| panic!("No constant {} in the Java layout", name));
+    // Read the record the way a Java client does, by the offsets in GestureDetectionLayout
+    let long = |field: &str| client.word_at(constant(&format!("RESULT_{}_OFFSET", field)) as usize).load(Ordering::Acquire);
+    let double = |field: &str| f64::from_bits(long(field) as u64);
+
+    let swipe = straight_swipe((300.0, -40.0));
+    assert_eq!(daemon.detect(200, -1, swipe.clone()), GestureDetectionServer::encode_result(
+        GenericGestureType::Swipe,
+        Some(DirectionResult::Drag(Direction::East)),
+    ));
+    let record = daemon.result_record().unwrap();
+    assert_eq!(long("GESTURE_TYPE"), constant("GESTURE_TYPE_SWIPE"));
+    assert_eq!(record.gesture, Some(GenericGestureType::Swipe));
+    assert_eq!(long("DIRECTION"), Direction::East.java_ordinal() as i64);
+    assert_eq!(record.direction_result, DirectionResult::Drag(Direction::East));
+    assert_eq!((long("ALTERNATIVE_DIRECTION"), long("ROTATION")), (constant("NO_ORDINAL"), constant("NO_ORDINAL")));
+    assert_eq!((long("TAPS"), long("ERROR_CODE")), (0, 0));
+    assert_eq!(double("CONFIDENCE"), record.confidence);
+    let bounding_box = (double("MIN_X"), double("MIN_Y"), double("MAX_X"), double("MAX_Y"));
+    assert_eq!(bounding_box, (0.0, -40.0, 300.0, 0.0));
+    assert_eq!(record.bounding_box.map(|(min, max)| (min.x, min.y, max.x, max.y)), Some(bounding_box));
+    let centroid = record.centroid.unwrap();
+    assert_eq!((double("CENTROID_X"), double("CENTROID_Y")), (centroid.x, centroid.y));
+    assert!((centroid.x - 150.0).abs() < 1e-9 && (centroid.y + 20.0).abs() < 1e-9, "centroid {:?}", centroid);
+    assert_eq!(double("PATH_LENGTH"), record.path_length);
+    assert!((record.path_length - Point::path_length(&swipe)).abs() < 1e-9);
+    assert_eq!((long("TEMPLATE_ID"), record.template_id), (constant("NO_TEMPLATE"), None));
+
+    // A template match names the template in the record
+    let letter_l = daemon.register_template("L", stroke(&LETTER_L));
+    assert_eq!(stream_gesture(&daemon, 300, &redraw(&stroke(&LETTER_L), 5.0)), GestureDetectionServer::encode_template_result());
+    let record = daemon.result_record().unwrap();
+    assert_eq!(long("TEMPLATE_ID"), letter_l);
+    assert_eq!(record.template_id, Some(letter_l as u32));
+
+    // Errors decode the same way on both sides
+    let header_length = constant("HEADER_LENGTH");
+    assert_eq!(header_length, client.layout.header_length as i64);
+    client.detect_raw(constant("POINT_CAPACITY") + 1, header_length);
+    let record = daemon.result_record().unwrap();
+    assert_eq!(long("ERROR_CODE"), constant("ERROR_CODE_INVALID_POINT_ARRAY"));
+    assert_eq!(record.error, Some(DetectionError::InvalidPointArray));
+    assert_eq!(long("GESTURE_TYPE"), constant("NO_ORDINAL"));
+    assert_eq!(record.gesture, None);
+    assert_eq!(double("CONFIDENCE"), 0.0);
+    daemon.stop();
+}
+
+/// Send `points` through the command ring, one COLLECT or INSERT per point, then DETECT.
+fn stream_gesture(daemon: &GestureDetectionDaemon, duration: u64, points: &[Point]) -> i64 {
+    assert_ne!(daemon.begin_stream(), -1);
+    for (index, &point) in points.iter().enumerate() {
+        let command = if index == 0 { ClientCommand::Collect { point } } else { ClientCommand::Insert { point } };
+        assert_ne!(daemon.send(command), -1);
+    }
+    daemon.send(ClientCommand::Detect { duration, count: points.len() as u32, fling_direction: None })
+}
+
+#[test]
+fn test_streamed_gestures_are_detected() {
+    let test_cases = parse_test_data(&read_to_string("src/core/test_data.txt").unwrap()).unwrap();
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_stream_test_{}", std::process::id()));
+    for (index, test_case) in test_cases.iter().enumerate() {
+        let expected = GestureDetectionServer::encode_result(test_case.generic_gesture_type, Some(test_case.direction_result));
+        assert_eq!(stream_gesture(&daemon, test_case.duration, &test_case.touch_points), expected, "case {}", index);
+    }
+
+    // Streaming is not limited by the point array or the command ring
+    let long_swipe = (0..10 * COMMAND_RING_CAPACITY.max(POINT_ARRAY_SIZE)).map(|i| Point::new(i as f64, i as f64)).collect::<Vec<_>>();
+    let mut detection = GestureDetection::new(200, long_swipe.clone(), None);
+    let (generic_gesture_type, maybe_direction) = determine_gesture_rule_based(&mut long_swipe.clone(), &mut detection);
+    assert_eq!(generic_gesture_type, GenericGestureType::Swipe);
+    assert_eq!(stream_gesture(&daemon, 200, &long_swipe), GestureDetectionServer::encode_result(generic_gesture_type, maybe_direction));
+
+    // Regular requests still work once the stream has ended
+    assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    daemon.stop();
+}
+
+#[test]
+fn test_provisional_classification_while_streaming() {
+    let mut detection = GestureDetection::new(0, Vec::new(), None);
+    let (generic_gesture_type, _) = detection.add_point(Point::sampled(0.0, 0.0, Some(0.0), None), &RuleBasedDetector);
+    assert_eq!(generic_gesture_type, GenericGestureType::Click);
+    assert!(detection.provisional_confidence() < 1.0);
+
+    let mut provisional = Vec::new();
+    for i in 1..=30 {
+        let point = Point::sampled(10.0 * i as f64, -10.0 * i as f64, Some(10.0 * i as f64), None);
+        provisional.push(detection.add_point(point, &RuleBasedDetector));
+    }
+    assert_eq!(detection.duration, 300);
+    // Once it is long enough, the stroke stays a swipe towards the north east and becomes certain
+    assert_eq!(provisional[2].0, GenericGestureType::Click);
+    assert_eq!(provisional.last().unwrap().0, GenericGestureType::Swipe);
+    assert_eq!(detection.provisional, Some((GenericGestureType::Swipe, DirectionResult::Drag(Direction::Northeast))));
+    assert_eq!(detection.provisional_confidence(), 1.0);
+}
+
+#[test]
+fn test_provisional_result_is_published_before_detect() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_provisional_test_{}", std::process::id()));
+    let swipe = (0..30).map(|i| Point::new(10.0 * i as f64, 0.0)).collect::<Vec<_>>();
+    let mut detection = GestureDetection::new(300, swipe.clone(), None);
+    let (generic_gesture_type, maybe_direction) = determine_gesture_rule_based(&mut swipe.clone(), &mut detection);
+    let expected = GestureDetectionServer::encode_result(generic_gesture_type, maybe_direction);
+
+    assert_ne!(daemon.begin_stream(), -1);
+    assert_eq!(daemon.provisional_result(), None);
+    daemon.send(ClientCommand::Collect { point: swipe[0] });
+    for &point in &swipe[1..] {
+        daemon.send(ClientCommand::Insert { point });
+    }
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while daemon.provisional_result() != Some((expected, 1.0)) {
+        assert!(Instant::now() < deadline, "provisional result is {:?}", daemon.provisional_result());
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let count = swipe.len() as u32;
+    assert_eq!(daemon.send(ClientCommand::Detect { duration: 300, count, fling_direction: None }), expected);
+    daemon.stop();
+}
+
+#[test]
+fn test_streamed_gesture_reset_and_lost_points() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_stream_reset_test_{}", std::process::id()));
+    let error = 1 << ERROR_BITWISE_OFFSET;
+
+    assert_ne!(daemon.begin_stream(), -1);
+    daemon.send(ClientCommand::Collect { point: Point::new(0.0, 0.0) });
+    daemon.send(ClientCommand::Insert { point: Point::new(300.0, 0.0) });
+    daemon.send(ClientCommand::Reset);
+    daemon.send(ClientCommand::Insert { point: Point::new(1.0, 1.0) });
+    // The count tells the server that the two points before the reset are not part of the gesture
+    assert_eq!(daemon.send(ClientCommand::Detect { duration: 100, count: 3, fling_direction: None }), error);
+
+    assert_eq!(stream_gesture(&daemon, 100, &[Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    daemon.stop();
+}
+
+#[test]
+fn test_halt_stops_a_stream_promptly() {
+    let name = format!("/gesture_detection_stream_halt_test_{}", std::process::id());
+    let server = GestureDetectionServer::new(SharedMemorySegment::create(&name).unwrap());
+    let client = CompositeGestureDetectionClient::attach(&name);
+    let layout_client = LayoutClient::attach(&name);
+    let server_thread = thread::spawn(move || server.serve());
+
+    assert_ne!(client.begin_stream(), -1);
+    assert_ne!(client.send(ClientCommand::Collect { point: Point::new(0.0, 0.0) }), -1);
+    // The server is asleep on the command ring; HALT has to wake it before the client gives up
+    client.halt();
+    assert_eq!(layout_client.read("server_state"), layout_client.constant("SERVER_STATE_EXITED"));
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_timestamps_and_pressure_round_trip_through_the_segment() {
+    let name = format!("/gesture_detection_samples_test_{}", std::process::id());
+    let client = GestureDetectionIPC::new(SharedMemorySegment::create(&name).unwrap());
+    let server = GestureDetectionIPC::new(SharedMemorySegment::open(&name).unwrap());
+    let points = vec![
+        Point::sampled(1.0, 2.0, Some(0.0), Some(0.5)),
+        Point::sampled(3.0, 4.0, Some(16.0), None),
+        Point::new(5.0, 6.0),
+    ];
+    client.write_points(&points).unwrap();
+
+    let received = server.read_points().unwrap();
+    assert_eq!(received, points);
+    assert_eq!(received.iter().map(|point| point.timestamp).collect::<Vec<_>>(), vec![Some(0.0), Some(16.0), None]);
+    assert_eq!(received.iter().map(|point| point.pressure).collect::<Vec<_>>(), vec![Some(0.5), None, None]);
+}
+
+#[test]
+fn test_point_geometry() {
+    let square = [Point::new(0.0, 0.0), Point::new(2.0, 0.0), Point::new(2.0, 2.0), Point::new(0.0, 2.0)];
+    assert_eq!(Point::centroid(&square), Point::new(1.0, 1.0));
+    assert!(Point::centroid(&[]).x.is_nan());
+    assert!((Point::mean_distance(&square, &Point::new(1.0, 1.0)) - 2f64.sqrt()).abs() < 1e-12);
+    assert_eq!(Point::mean_distance(&[], &Point::new(1.0, 1.0)), 0.0);
+    assert_eq!(Point::path_length(&square), 6.0);
+    assert_eq!(Point::path_length(&square[..1]), 0.0);
+    assert_eq!(Direction::normalize_degrees(270.0), -90.0);
+    assert_eq!(Direction::normalize_degrees(-180.0), 180.0);
+}
+
+#[test]
+fn test_velocity_from_timestamps() {
+    let detection = GestureDetection::new(30, vec![
+        Point::sampled(0.0, 0.0, Some(0.0), None),
+        Point::sampled(10.0, 0.0, Some(10.0), None),
+        Point::sampled(10.0, 40.0, Some(30.0), None),
+    ], None);
+    assert_eq!(detection.velocity_at(0), Some(Point::new(1.0, 0.0)));
+    assert_eq!(detection.velocity_at(2), Some(Point::new(0.0, 2.0)));
+    assert_eq!(detection.velocity_at(3), None);
+    assert_eq!(detection.average_velocity(), Some(Point::new(10.0 / 30.0, 40.0 / 30.0)));
+    assert_eq!(detection.average_speed(), Some(50.0 / 30.0));
+
+    let untimed = GestureDetection::new(30, vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)], None);
+    assert_eq!(untimed.velocity_at(1), None);
+    assert_eq!(untimed.average_speed(), None);
+}
+
+/// Move each finger in a straight line from its start to its end, interleaving the fingers' points
+/// the way Android reports them, one pointer id per finger.
+fn multi_touch(fingers: &[((f64, f64), (f64, f64))]) -> Vec<Point> {
+    const STEPS: usize = 4;
+    (0..=STEPS)
+        .flat_map(|step| {
+            let t = step as f64 / STEPS as f64;
+            fingers.iter().enumerate().map(move |(pointer_id, &((x0, y0), (x1, y1)))| {
+                Point::new(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t).on_pointer(pointer_id as u32)
+            })
+        })
+        .collect()
+}
+
+fn classify(duration: i128, points: Vec<Point>) -> (GenericGestureType, Option<DirectionResult>) {
+    let mut detection = GestureDetection::new(duration, points.clone(), None);
+    determine_gesture_rule_based(&mut points.clone(), &mut detection)
+}
+
+#[test]
+fn test_multi_touch_gestures_are_detected() {
+    let pinch = multi_touch(&[((0.0, 0.0), (150.0, 0.0)), ((400.0, 0.0), (250.0, 0.0))]);
+    assert_eq!(classify(300, pinch), (GenericGestureType::Pinch, Some(DirectionResult::Scale(0.25))));
+
+    let spread = multi_touch(&[((150.0, 0.0), (0.0, 0.0)), ((250.0, 0.0), (400.0, 0.0))]);
+    assert_eq!(classify(300, spread), (GenericGestureType::Spread, Some(DirectionResult::Scale(4.0))));
+
+    // Screen coordinates grow downwards, so moving from the right to the top is anticlockwise
+    let rotation = multi_touch(&[((100.0, 0.0), (0.0, -100.0)), ((-100.0, 0.0), (0.0, 100.0))]);
+    match classify(300, rotation) {
+        (GenericGestureType::Rotate, Some(DirectionResult::Rotation(RotationDirection::AntiClockwise, angle))) => {
+            assert!((angle - 90.0).abs() < 1e-9, "angle {}", angle)
+        }
+        other => panic!("expected an anticlockwise rotation, got {:?}", other),
+    }
+
+    let two_finger_swipe = multi_touch(&[((0.0, 0.0), (0.0, 300.0)), ((80.0, 0.0), (80.0, 300.0))]);
+    assert_eq!(
+        classify(300, two_finger_swipe),
+        (GenericGestureType::MultiSwipe, Some(DirectionResult::MultiDrag(Direction::South, 2)))
+    );
+
+    let three_finger_swipe = multi_touch(&[((0.0, 0.0), (300.0, 0.0)), ((0.0, 80.0), (300.0, 80.0)), ((0.0, 160.0), (300.0, 160.0))]);
+    assert_eq!(
+        classify(300, three_finger_swipe),
+        (GenericGestureType::MultiSwipe, Some(DirectionResult::MultiDrag(Direction::East, 3)))
+    );
+
+    let two_finger_tap = multi_touch(&[((0.0, 0.0), (2.0, 1.0)), ((80.0, 0.0), (81.0, 2.0))]);
+    assert_eq!(classify(100, two_finger_tap.clone()), (GenericGestureType::Click, None));
+    assert_eq!(classify(600, two_finger_tap), (GenericGestureType::Hold, None));
+
+    // Fingers heading different ways are not a swipe, even when their centroid moves far enough
+    let diverging = multi_touch(&[((0.0, 0.0), (0.0, 300.0)), ((80.0, 0.0), (380.0, -300.0))]);
+    assert_ne!(classify(300, diverging).0, GenericGestureType::MultiSwipe);
+}
+
+#[test]
+fn test_multi_touch_results_round_trip_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_multi_touch_test_{}", std::process::id()));
+
+    let pinch = multi_touch(&[((0.0, 0.0), (150.0, 0.0)), ((400.0, 0.0), (250.0, 0.0))]);
+    let expected = GestureDetectionServer::encode_result(GenericGestureType::Pinch, Some(DirectionResult::Scale(0.25)));
+    assert_eq!(daemon.detect(300, -1, pinch), expected);
+    assert_eq!(daemon.result_parameter(), 0.25);
+
+    let swipe = multi_touch(&[((0.0, 0.0), (300.0, 0.0)), ((0.0, 80.0), (300.0, 80.0)), ((0.0, 160.0), (300.0, 160.0))]);
+    let expected = GestureDetectionServer::encode_result(GenericGestureType::MultiSwipe, Some(DirectionResult::MultiDrag(Direction::East, 3)));
+    assert_eq!(stream_gesture(&daemon, 300, &swipe), expected);
+    assert_eq!(daemon.result_parameter(), 3.0);
+
+    // Single-finger results carry no parameter
+    assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    assert_eq!(daemon.result_parameter(), 0.0);
+    daemon.stop();
+}
+
+/// A finger resting at the origin for `hold` milliseconds, with a little jitter, then dragged to
+/// `end` over 200ms.
+fn hold_then_drag(hold: f64, end: (f64, f64)) -> Vec<Point> {
+    let mut points = (0..=4)
+        .map(|i| Point::sampled(3.0 * (i % 2) as f64, 2.0 * (i % 2) as f64, Some(hold * i as f64 / 4.0), None))
+        .collect::<Vec<_>>();
+    points.extend((1..=5).map(|i| {
+        let t = i as f64 / 5.0;
+        Point::sampled(end.0 * t, end.1 * t, Some(hold + 200.0 * t), None)
+    }));
+    points
+}
+
+#[test]
+fn test_hold_then_drag_is_detected() {
+    assert_eq!(
+        classify(800, hold_then_drag(600.0, (300.0, 0.0))),
+        (GenericGestureType::HoldDrag, Some(DirectionResult::Drag(Direction::East)))
+    );
+    assert_eq!(
+        classify(800, hold_then_drag(600.0, (0.0, -300.0))),
+        (GenericGestureType::HoldDrag, Some(DirectionResult::Drag(Direction::North)))
+    );
+    // A short pause before moving is still a swipe
+    assert_eq!(
+        classify(300, hold_then_drag(100.0, (300.0, 0.0))),
+        (GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East)))
+    );
+    // Without timestamps the pause cannot be told apart from a slow start
+    let untimed = hold_then_drag(600.0, (300.0, 0.0)).iter().map(|point| Point::new(point.x, point.y)).collect();
+    assert_eq!(classify(800, untimed), (GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East))));
+}
+
+#[test]
+fn test_hold_then_drag_round_trips_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_hold_drag_test_{}", std::process::id()));
+    let expected = GestureDetectionServer::encode_result(GenericGestureType::HoldDrag, Some(DirectionResult::Drag(Direction::South)));
+    assert_ne!(expected, GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::South))));
+    assert_eq!(daemon.detect(800, -1, hold_then_drag(600.0, (0.0, 300.0))), expected);
+    assert_eq!(stream_gesture(&daemon, 800, &hold_then_drag(600.0, (0.0, 300.0))), expected);
+    daemon.stop();
+}
+
+#[test]
+fn test_compass_points_split_the_directions() {
+    assert_eq!(CompassPoint::from_alpha(0.0), CompassPoint::East);
+    assert_eq!(CompassPoint::from_alpha(11.2), CompassPoint::East);
+    assert_eq!(CompassPoint::from_alpha(11.3), CompassPoint::EastNortheast);
+    assert_eq!(CompassPoint::from_alpha(22.5), CompassPoint::EastNortheast);
+    assert_eq!(CompassPoint::from_alpha(90.0), CompassPoint::North);
+    assert_eq!(CompassPoint::from_alpha(247.5), CompassPoint::SouthSouthwest);
+    assert_eq!(CompassPoint::from_alpha(348.0), CompassPoint::EastSoutheast);
+    assert_eq!(CompassPoint::from_alpha(350.0), CompassPoint::East);
+    assert_eq!(CompassPoint::from_alpha(-90.0), CompassPoint::South);
+    for (index, compass_point) in CompassPoint::values().into_iter().enumerate() {
+        assert_eq!(CompassPoint::from_index(index), compass_point);
+        assert_eq!(CompassPoint::from_alpha(22.5 * index as f64), compass_point, "{}", compass_point.name());
+    }
+
+    // Up the screen and a little to the right is still East on 8 points, but not on 16
+    let (start, end) = (Point::new(0.0, 0.0), Point::new(300.0, -120.0));
+    assert_eq!(Direction::direction_from_points(start, end), Direction::East);
+    let alpha = Direction::angle_from(end, start);
+    assert_eq!(CompassPoint::from_alpha(alpha), CompassPoint::EastNortheast);
+    assert_eq!(Direction::compass_sector(alpha, CompassMode::EightPoint), Direction::East.java_ordinal());
+    assert_eq!(Direction::compass_sector(alpha, CompassMode::SixteenPoint), CompassPoint::EastNortheast.java_ordinal());
+    assert_eq!(Direction::compass_sector(-90.0, CompassMode::EightPoint), Direction::South.java_ordinal());
+}
+
+/// A straight swipe from the origin to `end`, sampled every 10%.
+fn straight_swipe(end: (f64, f64)) -> Vec<Point> {
+    (0..=10).map(|i| Point::new(end.0 * i as f64 / 10.0, end.1 * i as f64 / 10.0)).collect()
+}
+
+#[test]
+fn test_direction_angle_is_kept_alongside_the_direction() {
+    let mut detection = GestureDetection::new(200, straight_swipe((300.0, -120.0)), None);
+    let (generic_gesture_type, maybe_direction) = determine_gesture_rule_based(&mut detection.touch_points.clone(), &mut detection);
+    assert_eq!((generic_gesture_type, maybe_direction), (GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East))));
+    let angle = detection.direction_angle.unwrap();
+    assert!((angle - 120f64.atan2(300.0).to_degrees()).abs() < 1e-9, "angle {}", angle);
+
+    let mut detection = GestureDetection::new(100, vec![Point::new(1.0, 2.0)], None);
+    determine_gesture_rule_based(&mut detection.touch_points.clone(), &mut detection);
+    assert_eq!(detection.direction_angle, None);
+
+    let swipe = multi_touch(&[((0.0, 0.0), (0.0, 300.0)), ((80.0, 0.0), (80.0, 300.0))]);
+    let mut detection = GestureDetection::new(300, swipe.clone(), None);
+    determine_gesture_rule_based(&mut swipe.clone(), &mut detection);
+    assert_eq!(detection.direction_angle, Some(270.0));
+}
+
+#[test]
+fn test_direction_angle_round_trips_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_angle_test_{}", std::process::id()));
+    let expected = GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East)));
+    assert_eq!(daemon.detect(200, -1, straight_swipe((300.0, -120.0))), expected);
+    assert!((daemon.direction_angle().unwrap() - 21.8).abs() < 0.01);
+    assert_eq!(daemon.compass_sector(), Some(Direction::East.java_ordinal()));
+
+    // On 16 points the same heading is East-northeast, while `result` keeps its 8 directions
+    let sixteen_point = ConfigurationBlock { compass_mode: CompassMode::SixteenPoint.java_ordinal(), ..daemon.configuration() };
+    assert_eq!(daemon.configure(sixteen_point), 0);
+    assert_eq!(daemon.detect(200, -1, straight_swipe((300.0, -120.0))), expected);
+    assert_eq!(daemon.compass_sector(), Some(CompassPoint::EastNortheast.java_ordinal()));
+
+    assert_eq!(stream_gesture(&daemon, 200, &straight_swipe((-200.0, 20.0))), GestureDetectionServer::encode_result(
+        GenericGestureType::Swipe,
+        Some(DirectionResult::Drag(Direction::West)),
+    ));
+    assert_eq!(daemon.compass_sector(), Some(CompassPoint::West.java_ordinal()));
+
+    // Results without a heading clear it
+    assert_eq!(daemon.detect(100, -1, vec![Point::new(1.0, 2.0)]), GenericGestureType::Click.java_ordinal() as i64);
+    assert_eq!(daemon.direction_angle(), None);
+    assert_eq!(daemon.compass_sector(), None);
+
+    assert_eq!(daemon.configure(ConfigurationBlock { compass_mode: 2, ..sixteen_point }), -1);
+    daemon.stop();
+}
+
+#[test]
+fn test_direction_sectors_resolve_boundary_angles() {
+    use DirectionResult::{AmbiguousDrag, Drag};
+    let sectors = |dead_zone, policy| DirectionSectors::new(dead_zone, policy).unwrap();
+
+    let nearest = DirectionSectors::default();
+    assert_eq!(nearest.drag(22.0, None), Drag(Direction::East));
+    assert_eq!(nearest.drag(23.0, None), Drag(Direction::Northeast));
+
+    let ambiguous = sectors(5.0, BoundaryPolicy::Ambiguous);
+    assert_eq!(ambiguous.drag(17.0, None), Drag(Direction::East));
+    assert_eq!(ambiguous.drag(20.0, None), AmbiguousDrag(Direction::East, Direction::Northeast));
+    assert_eq!(ambiguous.drag(25.0, None), AmbiguousDrag(Direction::Northeast, Direction::East));
+    assert_eq!(ambiguous.drag(28.0, None), Drag(Direction::Northeast));
+    assert_eq!(ambiguous.drag(340.0, None), AmbiguousDrag(Direction::East, Direction::Southeast));
+    assert_eq!(ambiguous.drag(-22.0, None), AmbiguousDrag(Direction::East, Direction::Southeast));
+
+    let cardinal = sectors(10.0, BoundaryPolicy::Cardinal);
+    assert_eq!(cardinal.drag(15.0, None), Drag(Direction::East));
+    assert_eq!(cardinal.drag(30.0, None), Drag(Direction::East));
+    assert_eq!(cardinal.drag(60.0, None), Drag(Direction::North));
+    assert_eq!(cardinal.drag(200.0, None), Drag(Direction::West));
+    assert_eq!(cardinal.drag(45.0, None), Drag(Direction::Northeast));
+
+    // Within the dead zone a stroke keeps the direction it already had, if it is one of the two
+    assert_eq!(ambiguous.drag(20.0, Some(Direction::Northeast)), Drag(Direction::Northeast));
+    assert_eq!(cardinal.drag(30.0, Some(Direction::Northeast)), Drag(Direction::Northeast));
+    assert_eq!(sectors(10.0, BoundaryPolicy::Nearest).drag(15.0, Some(Direction::Northeast)), Drag(Direction::Northeast));
+    assert_eq!(ambiguous.drag(20.0, Some(Direction::North)), AmbiguousDrag(Direction::East, Direction::Northeast));
+    assert_eq!(ambiguous.drag(10.0, Some(Direction::Northeast)), Drag(Direction::East));
+
+    assert!(DirectionSectors::new(-1.0, BoundaryPolicy::Ambiguous).is_err());
+    assert!(DirectionSectors::new(30.0, BoundaryPolicy::Ambiguous).is_err());
+    assert!(DirectionSectors::new(f64::NAN, BoundaryPolicy::Ambiguous).is_err());
+    assert!(Configuration::from_block(&ConfigurationBlock { direction_dead_zone: 5.0, direction_boundary_policy: 7, ..Configuration::default().to_block() }).is_err());
+}
+
+#[test]
+fn test_multi_finger_swipes_resolve_boundary_angles() {
+    // Two fingers heading 30 degrees up from East, 7.5 degrees past the boundary with Northeast
+    let (dx, dy) = (300.0 * 30f64.to_radians().cos(), -300.0 * 30f64.to_radians().sin());
+    let swipe = multi_touch(&[((0.0, 0.0), (dx, dy)), ((0.0, 80.0), (dx, 80.0 + dy))]);
+    let classify_with = |sectors| {
+        let mut detection = GestureDetection::new(300, swipe.clone(), None);
+        detection.direction_sectors = sectors;
+        determine_gesture_rule_based(&mut swipe.clone(), &mut detection)
+    };
+    let multi_swipe = |direction| (GenericGestureType::MultiSwipe, Some(DirectionResult::MultiDrag(direction, 2)));
+
+    assert_eq!(classify_with(DirectionSectors::default()), multi_swipe(Direction::Northeast));
+    assert_eq!(classify_with(DirectionSectors::new(10.0, BoundaryPolicy::Cardinal).unwrap()), multi_swipe(Direction::East));
+    assert_eq!(classify_with(DirectionSectors::new(10.0, BoundaryPolicy::Ambiguous).unwrap()), multi_swipe(Direction::Northeast));
+    assert_eq!(classify_with(DirectionSectors::new(5.0, BoundaryPolicy::Cardinal).unwrap()), multi_swipe(Direction::Northeast));
+}
+
+/// A swipe that heads 35 degrees up from East, then bends towards East, so that its overall
+/// heading ends up just past the boundary between Northeast and East.
+fn bending_swipe() -> Vec<Point> {
+    let (cos, sin) = (35f64.to_radians().cos(), 35f64.to_radians().sin());
+    let mut points = (0..=15).map(|i| Point::new(10.0 * i as f64 * cos, -10.0 * i as f64 * sin)).collect::<Vec<_>>();
+    let corner = *points.last().unwrap();
+    points.extend((1..=30).map(|j| corner.add(Point::new(10.0 * j as f64, 0.0))));
+    points
+}
+
+#[test]
+fn test_streamed_strokes_keep_their_direction_near_a_boundary() {
+    let sectors = DirectionSectors::new(10.0, BoundaryPolicy::Ambiguous).unwrap();
+    let mut detection = GestureDetection::new(0, Vec::new(), None);
+    detection.direction_sectors = sectors;
+    for point in bending_swipe() {
+        detection.add_point(point, &RuleBasedDetector);
+    }
+    assert_eq!(detection.provisional, Some((GenericGestureType::Swipe, DirectionResult::Drag(Direction::Northeast))));
+
+    // Seen in one go, the same stroke is on the boundary
+    let mut detection = GestureDetection::new(300, bending_swipe(), None);
+    detection.direction_sectors = sectors;
+    assert_eq!(
+        determine_gesture_rule_based(&mut bending_swipe(), &mut detection),
+        (GenericGestureType::Swipe, Some(DirectionResult::AmbiguousDrag(Direction::Northeast, Direction::East)))
+    );
+}
+
+#[test]
+fn test_direction_sectors_are_configured_over_ipc() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_configure_test_{}", std::process::id()));
+    let swipe = |direction_result| GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(direction_result));
+    assert_eq!(daemon.configuration(), Configuration::default().to_block());
+    assert_eq!(daemon.detect(300, -1, bending_swipe()), swipe(DirectionResult::Drag(Direction::Northeast)));
+
+    let ambiguous = ConfigurationBlock { direction_dead_zone: 10.0, direction_boundary_policy: BoundaryPolicy::Ambiguous.java_ordinal(), ..Configuration::default().to_block() };
+    assert_eq!(daemon.configure(ambiguous), 0);
+    assert_eq!(daemon.configuration(), ambiguous);
+    let expected = swipe(DirectionResult::AmbiguousDrag(Direction::Northeast, Direction::East));
+    assert_eq!(expected.count_ones(), 2);
+    assert_eq!(daemon.detect(300, -1, bending_swipe()), expected);
+    assert_eq!(stream_gesture(&daemon, 300, &bending_swipe()), swipe(DirectionResult::Drag(Direction::Northeast)));
+
+    // A rejected configuration leaves the previous one in place
+    let too_wide = ConfigurationBlock { direction_dead_zone: 40.0, direction_boundary_policy: BoundaryPolicy::Cardinal.java_ordinal(), ..Configuration::default().to_block() };
+    assert_eq!(daemon.configure(too_wide), -1);
+    assert_eq!(daemon.configuration(), ambiguous);
+
+    let cardinal = ConfigurationBlock { direction_dead_zone: 10.0, direction_boundary_policy: BoundaryPolicy::Cardinal.java_ordinal(), ..Configuration::default().to_block() };
+    assert_eq!(daemon.configure(cardinal), 0);
+    assert_eq!(daemon.detect(300, -1, bending_swipe()), swipe(DirectionResult::Drag(Direction::East)));
+    daemon.stop();
+}
+
+/// Interpolate between the corners of a stroke, so that it is sampled like a real touch.
+fn stroke(corners: &[(f64, f64)]) -> Vec<Point> {
+    let mut points = vec![Point::new(corners[0].0, corners[0].1)];
+    for pair in corners.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        points.extend((1..=8).map(|i| {
+            let t = i as f64 / 8.0;
+            Point::new(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+        }));
+    }
+    points
+}
+
+/// The same stroke drawn elsewhere, larger, and turned a little.
+fn redraw(points: &[Point], degrees: f64) -> Vec<Point> {
+    points.iter().map(|point| point.rotate(&Point::new(0.0, 0.0), degrees).mul(1.7).add(Point::new(300.0, 500.0))).collect()
+}
+
+const CHECKMARK: [(f64, f64); 3] = [(0.0, 0.0), (40.0, 50.0), (120.0, -90.0)];
+const ZIG_ZAG: [(f64, f64); 5] = [(0.0, 0.0), (50.0, -60.0), (100.0, 0.0), (150.0, -60.0), (200.0, 0.0)];
+const LETTER_L: [(f64, f64); 3] = [(0.0, 0.0), (0.0, 150.0), (100.0, 150.0)];
+
+#[test]
+fn test_template_recognizer_matches_redrawn_strokes() {
+    let mut recognizer = TemplateRecognizer::default();
+    let checkmark = recognizer.add_template("checkmark", &stroke(&CHECKMARK)).unwrap();
+    let zig_zag = recognizer.add_template("zig-zag", &stroke(&ZIG_ZAG)).unwrap();
+    let letter_l = recognizer.add_template("L", &stroke(&LETTER_L)).unwrap();
+    assert!(recognizer.add_template("dot", &[Point::new(1.0, 1.0), Point::new(1.0, 1.0)]).is_err());
+
+    for (corners, id) in [(&CHECKMARK[..], checkmark), (&ZIG_ZAG[..], zig_zag), (&LETTER_L[..], letter_l)] {
+        for degrees in [-20.0, 0.0, 15.0] {
+            let (template, score) = recognizer.recognize(&redraw(&stroke(corners), degrees)).expect("no template matched");
+            assert_eq!(template.id, id, "rotated by {} degrees", degrees);
+            assert!(score > 0.9, "score {} for {} rotated by {} degrees", score, template.name, degrees);
+        }
+    }
+    // A circle looks like none of them
+    let circle = (0..=32)
+        .map(|i| Point::new(100.0 * (i as f64 / 32.0 * TAU).cos(), 100.0 * (i as f64 / 32.0 * TAU).sin()))
+        .collect::<Vec<_>>();
+    assert!(recognizer.recognize(&circle).is_none());
+}
+
+#[test]
+fn test_template_matches_round_trip_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_template_test_{}", std::process::id()));
+    let zig_zag = daemon.add_template("zig-zag", &stroke(&ZIG_ZAG)).unwrap();
+
+    // Too many points for the point array, so stream them
+    let result = stream_gesture(&daemon, 300, &redraw(&stroke(&ZIG_ZAG), 10.0));
+    assert_eq!(result, GestureDetectionServer::encode_template_result());
+    assert_eq!(daemon.template_id(), Some(zig_zag));
+    assert!(daemon.result_parameter() > 0.9);
+
+    // Strokes that match no template are reported as usual
+    let swipe = stroke(&[(0.0, 0.0), (300.0, 0.0)]);
+    assert_eq!(daemon.detect(300, -1, swipe), GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East))));
+    assert_eq!(daemon.template_id(), None);
+    daemon.stop();
+}
+
+/// The rule-based detector, counting how often it is run.
+#[derive(Debug, Default)]
+struct CountingDetector {
+    detections: AtomicUsize,
+}
+
+impl GestureDetector for CountingDetector {
+    fn name(&self) -> String {
+        String::from("COUNTING")
+    }
+
+    fn detect(
+        &self,
+        touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>) {
+        self.detections.fetch_add(1, Ordering::Relaxed);
+        RuleBasedDetector.detect(touch_points, gesture_detection)
+    }
+}
+
+#[test]
+fn test_long_streams_are_reclassified_less_often() {
+    let detector = CountingDetector::default();
+    let mut detection = GestureDetection::new(0, Vec::new(), None);
+    for i in 0..32 {
+        detection.add_point(Point::new(i as f64, 0.0), &detector);
+    }
+    assert_eq!(detector.detections.load(Ordering::Relaxed), 32);
+    for i in 32..2000 {
+        detection.add_point(Point::new(i as f64, 0.0), &detector);
+    }
+    let detections = detector.detections.load(Ordering::Relaxed);
+    assert!(detections < 200, "{} classifications for 2000 points", detections);
+    // In between, the last provisional result stands
+    assert_eq!(detection.provisional, Some((GenericGestureType::Swipe, DirectionResult::Drag(Direction::East))));
+    assert_eq!(detection.provisional_confidence(), 1.0);
+}
+
+#[test]
+fn test_provisional_holds_need_timestamps() {
+    let mut timed = GestureDetection::new(0, Vec::new(), None);
+    let mut untimed = GestureDetection::new(0, Vec::new(), None);
+    for i in 0..10 {
+        timed.add_point(Point::sampled(1.0, 1.0, Some(60.0 * i as f64), None), &RuleBasedDetector);
+        untimed.add_point(Point::new(1.0, 1.0), &RuleBasedDetector);
+    }
+    assert_eq!(timed.provisional.map(|(gesture, _)| gesture), Some(GenericGestureType::Hold));
+    assert_eq!(untimed.provisional.map(|(gesture, _)| gesture), Some(GenericGestureType::Click));
+}
+
+/// Reports every gesture as a swipe to the West, to tell it apart from the built-in detectors.
+#[derive(Debug)]
+struct WestwardDetector;
+
+impl GestureDetector for WestwardDetector {
+    fn name(&self) -> String {
+        String::from("WESTWARD")
+    }
+
+    fn detect(
+        &self,
+        _touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>) {
+        gesture_detection.direction_result = DirectionResult::Drag(Direction::West);
+        (GenericGestureType::Swipe, Some(gesture_detection.direction_result))
+    }
+}
+
+#[test]
+fn test_detectors_are_selected_through_the_configuration() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_detector_test_{}", std::process::id()));
+    let westward = daemon.register_detector(Arc::new(WestwardDetector));
+    assert_eq!(westward, 3);
+    let zig_zag = daemon.add_template("zig-zag", &stroke(&ZIG_ZAG)).unwrap();
+    let drawn = redraw(&stroke(&ZIG_ZAG), 10.0);
+    let with_detector = |detector: u32| ConfigurationBlock { detector: detector as i64, ..Configuration::default().to_block() };
+
+    // Templates are only matched by the template-based detector, which is the default
+    assert_eq!(daemon.configuration().detector, TEMPLATE_BASED_DETECTOR as i64);
+    assert_eq!(stream_gesture(&daemon, 300, &drawn), GestureDetectionServer::encode_template_result());
+    assert_eq!(daemon.template_id(), Some(zig_zag));
+    assert_eq!(daemon.configure(with_detector(RULE_BASED_DETECTOR)), 0);
+    assert_ne!(stream_gesture(&daemon, 300, &drawn), GestureDetectionServer::encode_template_result());
+    assert_eq!(daemon.template_id(), None);
+
+    let swipe = straight_swipe((300.0, 0.0));
+    assert_eq!(daemon.configure(with_detector(DUMMY_DETECTOR)), 0);
+    assert_eq!(daemon.detect(200, -1, swipe.clone()), GestureDetectionServer::encode_result(GenericGestureType::Click, None));
+    // Streamed points are classified by the selected detector as well
+    assert_eq!(daemon.configure(with_detector(westward)), 0);
+    let west = GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::West)));
+    assert_eq!(stream_gesture(&daemon, 200, &swipe), west);
+    assert_eq!(daemon.provisional_result().map(|(result, _)| result), Some(west));
+
+    // Unknown detectors are rejected, keeping the one selected before
+    assert_eq!(daemon.configure(with_detector(4)), -1);
+    assert_eq!(daemon.configure(with_detector(u32::MAX)), -1);
+    assert_eq!(daemon.configure(ConfigurationBlock { detector: -1, ..with_detector(0) }), -1);
+    assert_eq!(daemon.configuration().detector, westward as i64);
+    assert_eq!(daemon.detect(200, -1, swipe), west);
+    daemon.stop();
+}
+
+#[test]
+fn test_thresholds_are_tuned_through_the_configuration() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_thresholds_test_{}", std::process::id()));
+    let click = GestureDetectionServer::encode_result(GenericGestureType::Click, None);
+    let hold = GestureDetectionServer::encode_result(GenericGestureType::Hold, None);
+    let east = GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East)));
+    let short_swipe = straight_swipe((60.0, 0.0));
+    let touch = vec![Point::new(500.0, 500.0), Point::new(501.0, 501.0)];
+    assert_eq!(daemon.detect(300, -1, short_swipe.clone()), click);
+    assert_eq!(daemon.detect(300, -1, touch.clone()), click);
+
+    // A touchpad with smaller movements and a quicker hold
+    let touchpad = ConfigurationBlock { hold_duration: 200, drag_length: 40.0, ..Configuration::default().to_block() };
+    assert_eq!(daemon.configure(touchpad), 0);
+    assert_eq!(daemon.configuration(), touchpad);
+    assert_eq!(daemon.detect(300, -1, short_swipe.clone()), east);
+    assert_eq!(daemon.detect(300, -1, touch.clone()), hold);
+    assert_eq!(stream_gesture(&daemon, 300, &short_swipe), east);
+    assert!(daemon.disqualifications().unwrap().iter().any(|disqualification| disqualification.rule == Rule::DragLength && disqualification.threshold == 40.0));
+
+    // None of a block is applied if any of it is invalid
+    assert_eq!(daemon.configure(ConfigurationBlock { hold_duration: 1000, circle_radius: 0.0, ..touchpad }), -1);
+    assert_eq!(daemon.configuration(), touchpad);
+    assert_eq!(daemon.detect(300, -1, touch), hold);
+
+    assert_eq!(daemon.configure(Configuration::default().to_block()), 0);
+    assert_eq!(daemon.detect(300, -1, short_swipe), click);
+    daemon.stop();
+
+    assert!(Thresholds::new(0, 30.0, 100.0).is_err());
+    assert!(Thresholds::new(400, f64::NAN, 100.0).is_err());
+    assert!(Thresholds::new(400, 30.0, f64::INFINITY).is_err());
+    assert_eq!(Thresholds::new(400, 30.0, 100.0), Ok(Thresholds::default()));
+}
+
+#[test]
+fn test_distances_are_measured_in_dp_of_the_display() {
+    let xxhdpi = Display::new(480.0, 1080.0, 2400.0).unwrap();
+    assert_eq!(xxhdpi.dp(300.0), 100.0);
+    let point = xxhdpi.to_dp(Point::sampled(30.0, 60.0, Some(5.0), None).on_pointer(1));
+    assert_eq!((point.x, point.y, point.timestamp, point.pointer_id), (10.0, 20.0, Some(5.0), 1));
+    assert_eq!(Display::default().dp(300.0), 300.0);
+    assert_eq!(Display::default().diagonal(), None);
+    assert!(Display::new(0.0, 1080.0, 2400.0).is_err());
+    assert!(Display::new(BASELINE_DENSITY, -1.0, 2400.0).is_err());
+
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_display_test_{}", std::process::id()));
+    let click = GestureDetectionServer::encode_result(GenericGestureType::Click, None);
+    let east = GestureDetectionServer::encode_result(GenericGestureType::Swipe, Some(DirectionResult::Drag(Direction::East)));
+    assert_eq!(daemon.detect(300, -1, straight_swipe((150.0, 0.0))), east);
+
+    // On a 480 dpi phone the same 150 pixels are only 50dp
+    let phone = ConfigurationBlock { display_density: 480.0, display_width: 1080.0, display_height: 2400.0, ..Configuration::default().to_block() };
+    assert_eq!(daemon.configure(phone), 0);
+    assert_eq!(daemon.detect(300, -1, straight_swipe((150.0, 0.0))), click);
+    assert_eq!(daemon.detect(300, -1, straight_swipe((330.0, 0.0))), east);
+    let drag_length = daemon.disqualifications().unwrap().into_iter().find(|disqualification| disqualification.rule == Rule::DragLength).unwrap();
+    assert!((drag_length.measured - 110.0).abs() < 1e-9);
+    assert_eq!(stream_gesture(&daemon, 300, &straight_swipe((330.0, 0.0))), east);
+
+    // A drag length that does not fit on the display could never be reached
+    let watch = ConfigurationBlock { display_width: 200.0, display_height: 100.0, ..phone };
+    assert_eq!(daemon.configure(watch), -1);
+    assert_eq!(daemon.configuration(), phone);
+    assert_eq!(daemon.configure(ConfigurationBlock { drag_length: 50.0, ..watch }), 0);
+    assert_eq!(daemon.detect(300, -1, straight_swipe((150.0, 0.0))), east);
+    daemon.stop();
+}
+
+#[test]
+fn test_templates_are_managed_over_ipc() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_template_ipc_test_{}", std::process::id()));
+    assert_eq!(daemon.list_templates(), Ok(vec![]));
+
+    let checkmark = daemon.register_template("checkmark", stroke(&CHECKMARK));
+    let letter_l = daemon.register_template("L", stroke(&LETTER_L));
+    assert_eq!((checkmark, letter_l), (0, 1));
+    assert_eq!(daemon.list_templates(), Ok(vec![(0, String::from("checkmark")), (1, String::from("L"))]));
+    assert_eq!(daemon.register_template("a name that is far too long", stroke(&CHECKMARK)), -1);
+    assert_eq!(daemon.register_template("dot", vec![Point::new(1.0, 1.0)]), -1);
+
+    assert_eq!(stream_gesture(&daemon, 300, &redraw(&stroke(&LETTER_L), 5.0)), GestureDetectionServer::encode_template_result());
+    assert_eq!(daemon.template_id(), Some(1));
+
+    assert_eq!(daemon.remove_template(1), 1);
+    assert_eq!(daemon.remove_template(1), -1);
+    assert_eq!(daemon.list_templates(), Ok(vec![(0, String::from("checkmark"))]));
+    assert_ne!(stream_gesture(&daemon, 300, &redraw(&stroke(&LETTER_L), 5.0)), GestureDetectionServer::encode_template_result());
+    daemon.stop();
+}
+
+#[test]
+fn test_templates_are_persisted_across_daemons() {
+    let template_file = std::env::temp_dir().join(format!("gesture_templates_test_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&template_file);
+
+    let mut daemon = GestureDetectionDaemon::with_template_file(&format!("/gesture_detection_template_file_test_{}", std::process::id()), &template_file);
+    assert_eq!(daemon.register_template("zig", stroke(&ZIG_ZAG[..3])), 0);
+    assert_eq!(daemon.register_template("checkmark", stroke(&CHECKMARK)), 1);
+    assert_eq!(daemon.remove_template(0), 0);
+    daemon.stop();
+
+    let mut daemon = GestureDetectionDaemon::with_template_file(&format!("/gesture_detection_template_file_test_{}", std::process::id()), &template_file);
+    assert_eq!(daemon.list_templates(), Ok(vec![(1, String::from("checkmark"))]));
+    assert_eq!(stream_gesture(&daemon, 300, &redraw(&stroke(&CHECKMARK), -10.0)), GestureDetectionServer::encode_template_result());
+    assert_eq!(daemon.template_id(), Some(1));
+    // Ids are not reused, even after a restart
+    assert_eq!(daemon.register_template("L", stroke(&LETTER_L)), 2);
+    daemon.stop();
+    std::fs::remove_file(&template_file).unwrap();
+}
+
+#[test]
+fn test_removed_template_ids_are_not_reused_after_a_reload() {
+    let template_file = std::env::temp_dir().join(format!("gesture_templates_next_id_test_{}.txt", std::process::id()));
+    let mut recognizer = TemplateRecognizer::default();
+    assert_eq!(recognizer.add_template("zig", &stroke(&ZIG_ZAG[..3])), Ok(0));
+    assert_eq!(recognizer.add_template("checkmark", &stroke(&CHECKMARK)), Ok(1));
+    // The newest template is the one whose id a reload could not tell was ever used
+    assert!(recognizer.remove_template(1));
+    recognizer.save(&template_file).unwrap();
+
+    let mut recognizer = TemplateRecognizer::load(&template_file).unwrap();
+    assert_eq!(recognizer.templates().iter().map(|template| template.id).collect::<Vec<_>>(), vec![0]);
+    assert_eq!(recognizer.add_template("L", &stroke(&LETTER_L)), Ok(2));
+    recognizer.save(&template_file).unwrap();
+
+    // Files written before the next id was saved continue after their highest id
+    std::fs::write(&template_file, read_to_string(&template_file).unwrap().split_once('\n').unwrap().1).unwrap();
+    assert_eq!(TemplateRecognizer::load(&template_file).unwrap().add_template("L", &stroke(&LETTER_L)), Ok(3));
+    std::fs::write(&template_file, "next_id\tmany\n").unwrap();
+    assert!(TemplateRecognizer::load(&template_file).is_err());
+    std::fs::remove_file(&template_file).unwrap();
+}
+
+#[test]
+fn test_tap_sequencer_groups_nearby_clicks() {
+    let mut sequencer = TapSequencer::default();
+    let thresholds = TapThresholds { maximum_interval: Duration::from_millis(300), maximum_distance: 50.0 };
+    let start = Instant::now();
+    let at = |millis: u64| TapTime { classified: start + Duration::from_millis(millis), sampled: None };
+    let mut register = |generic_gesture_type, x, y, ended| sequencer.register(&thresholds, generic_gesture_type, Point::new(x, y), ended);
+
+    assert_eq!(register(GenericGestureType::Click, 0.0, 0.0, at(0)), 1);
+    assert_eq!(register(GenericGestureType::Click, 10.0, 0.0, at(200)), 2);
+    assert_eq!(register(GenericGestureType::Click, 0.0, 20.0, at(400)), 3);
+    // Too late
+    assert_eq!(register(GenericGestureType::Click, 0.0, 0.0, at(800)), 1);
+    // Too far from where the sequence started
+    assert_eq!(register(GenericGestureType::Click, 80.0, 0.0, at(900)), 1);
+    assert_eq!(register(GenericGestureType::Click, 80.0, 0.0, at(1000)), 2);
+    // Any other gesture ends the sequence
+    assert_eq!(register(GenericGestureType::Swipe, 80.0, 0.0, at(1100)), 0);
+    assert_eq!(register(GenericGestureType::Click, 80.0, 0.0, at(1200)), 1);
+
+    // Timestamped taps are timed by their samples, however late the server classifies them
+    let sampled = |millis: u64, timestamp: f64| TapTime { sampled: Some(timestamp), ..at(millis) };
+    assert_eq!(register(GenericGestureType::Click, 0.0, 0.0, sampled(2000, 10_000.0)), 1);
+    assert_eq!(register(GenericGestureType::Click, 0.0, 0.0, sampled(3000, 10_250.0)), 2);
+    assert_eq!(register(GenericGestureType::Click, 0.0, 0.0, sampled(3010, 10_600.0)), 1);
+    // Without a timestamp on both taps, the server's clock is all there is
+    assert_eq!(register(GenericGestureType::Click, 0.0, 0.0, at(3100)), 2);
+}
+
+#[test]
+fn test_taps_round_trip_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_tap_test_{}", std::process::id()));
+    let click = GenericGestureType::Click.java_ordinal() as i64;
+    let tap = || vec![Point::new(1.0, 2.0)];
+
+    assert_eq!(daemon.detect(100, -1, tap()), click);
+    assert_eq!(daemon.detect(100, -1, tap()), GestureDetectionServer::encode_tap_result(2));
+    assert_eq!(daemon.result_parameter(), 2.0);
+    assert_eq!(stream_gesture(&daemon, 100, &tap()), GestureDetectionServer::encode_tap_result(3));
+    assert_eq!(daemon.result_parameter(), 3.0);
+    assert_eq!(daemon.detect(100, -1, tap()), GestureDetectionServer::encode_tap_result(4));
+    assert_eq!(daemon.result_parameter(), 4.0);
+
+    let quick = ConfigurationBlock { tap_interval: 20, ..daemon.configuration() };
+    assert_eq!(daemon.configure(quick), 0);
+    assert_eq!(daemon.detect(100, -1, tap()), click);
+    thread::sleep(Duration::from_millis(40));
+    assert_eq!(daemon.detect(100, -1, tap()), click);
+    assert_eq!(daemon.result_parameter(), 0.0);
+
+    // Samples taken 10ms apart are still a double tap, however long the client took to send them
+    let timestamped = |timestamp: f64| vec![Point::sampled(1.0, 2.0, Some(timestamp), None)];
+    thread::sleep(Duration::from_millis(40));
+    assert_eq!(daemon.detect(100, -1, timestamped(5_000.0)), click);
+    thread::sleep(Duration::from_millis(40));
+    assert_eq!(daemon.detect(100, -1, timestamped(5_010.0)), GestureDetectionServer::encode_tap_result(2));
+
+    // A tap interval of 0 turns taps off, and negative or non-finite thresholds are rejected
+    assert_eq!(daemon.configure(ConfigurationBlock { tap_interval: 0, ..quick }), 0);
+    assert_eq!(daemon.detect(100, -1, tap()), click);
+    assert_eq!(daemon.detect(100, -1, tap()), click);
+    assert_eq!(daemon.configure(ConfigurationBlock { tap_interval: -1, ..quick }), -1);
+    assert_eq!(daemon.configure(ConfigurationBlock { tap_distance: f64::NAN, ..quick }), -1);
+    assert_eq!(daemon.configuration().tap_interval, 0);
+    daemon.stop();
+}
+
+#[test]
+fn test_result_records_encode_and_decode() {
+    let swipe = straight_swipe((300.0, -120.0));
+    let pinch = multi_touch(&[((0.0, 0.0), (150.0, 0.0)), ((400.0, 0.0), (250.0, 0.0))]);
+    let gesture_results = [
+        GestureResult::detected(GenericGestureType::Swipe, DirectionResult::Drag(Direction::East), 1.0, &swipe),
+        GestureResult::detected(
+            GenericGestureType::Boomerang,
+            DirectionResult::AmbiguousDrag(Direction::Northeast, Direction::North),
+            0.5,
+            &swipe,
+        ),
+        GestureResult::detected(GenericGestureType::MultiSwipe, DirectionResult::MultiDrag(Direction::South, 3), 1.0, &pinch),
+        GestureResult::detected(GenericGestureType::Rotate, DirectionResult::Rotation(RotationDirection::Clockwise, 42.0), 1.0, &pinch),
+        GestureResult::detected(GenericGestureType::Circle, DirectionResult::Circular(RotationDirection::AntiClockwise), 1.0, &swipe),
+        GestureResult::detected(GenericGestureType::Pinch, DirectionResult::Scale(0.25), 1.0, &pinch),
+        GestureResult { taps: 2, ..GestureResult::detected(GenericGestureType::Click, DirectionResult::None, 1.0, &[Point::new(1.0, 2.0)]) },
+        GestureResult { template_id: Some(7), ..GestureResult::detected(GenericGestureType::Swipe, DirectionResult::Drag(Direction::East), 1.0, &swipe) },
+        GestureResult::failed(DetectionError::MissingPoints, &swipe),
+        GestureResult::failed(DetectionError::InvalidPointArray, &[]),
+    ];
+    for gesture_result in gesture_results {
+        assert_eq!(ResultRecord::encode(&gesture_result).decode(), Ok(gesture_result));
+    }
+
+    let swipe_result = GestureResult::detected(GenericGestureType::Swipe, DirectionResult::Drag(Direction::East), 1.0, &swipe);
+    assert_eq!(swipe_result.bounding_box, Some((Point::new(0.0, -120.0), Point::new(300.0, 0.0))));
+    assert_eq!(swipe_result.centroid, Some(Point::new(150.0, -60.0)));
+    assert!((swipe_result.path_length - 300f64.hypot(120.0)).abs() < 1e-9);
+    // Each finger's path counts, but not the jumps between fingers
+    assert_eq!(GestureResult::measure(&pinch).path_length, 300.0);
+
+    let record = ResultRecord::encode(&swipe_result);
+    assert!(ResultRecord { gesture_type: 42, ..record }.decode().is_err());
+    assert!(ResultRecord { direction: -1, ..record }.decode().is_err());
+    assert!(ResultRecord { alternative_direction: 8, ..record }.decode().is_err());
+    assert!(ResultRecord { error_code: 9, ..record }.decode().is_err());
+    assert!(ResultRecord { taps: -1, ..record }.decode().is_err());
+    assert!(ResultRecord { template_id: -2, ..record }.decode().is_err());
+}
+
+#[test]
+fn test_result_records_round_trip_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_record_test_{}", std::process::id()));
+    assert_eq!(daemon.result_record(), Ok(GestureResult::measure(&[])));
+
+    let swipe = straight_swipe((300.0, -120.0));
+    daemon.detect(200, -1, swipe.clone());
+    let expected = GestureResult::detected(GenericGestureType::Swipe, DirectionResult::Drag(Direction::East), 1.0, &swipe);
+    assert_eq!(daemon.result_record(), Ok(expected));
+
+    let pinch = multi_touch(&[((0.0, 0.0), (150.0, 0.0)), ((400.0, 0.0), (250.0, 0.0))]);
+    stream_gesture(&daemon, 300, &pinch);
+    let expected = GestureResult::detected(GenericGestureType::Pinch, DirectionResult::Scale(0.25), 1.0, &pinch);
+    assert_eq!(daemon.result_record(), Ok(expected));
+
+    let tap = vec![Point::new(1.0, 2.0)];
+    daemon.detect(100, -1, tap.clone());
+    assert_eq!(daemon.result_record().unwrap().taps, 1);
+    daemon.detect(100, -1, tap.clone());
+    // Decisive on the pointer count and the drag length, but 100ms is a quarter of the way to a hold
+    let confidence = (1.0 + 1.0 + 0.875) / 3.0;
+    let expected = GestureResult { taps: 2, ..GestureResult::detected(GenericGestureType::Click, DirectionResult::None, confidence, &tap) };
+    assert_eq!(daemon.result_record(), Ok(expected));
+
+    assert_ne!(daemon.begin_stream(), -1);
+    daemon.send(ClientCommand::Collect { point: Point::new(0.0, 0.0) });
+    daemon.send(ClientCommand::Detect { duration: 100, count: 2, fling_direction: None });
+    let gesture_result = daemon.result_record().unwrap();
+    assert_eq!((gesture_result.gesture, gesture_result.error), (None, Some(DetectionError::MissingPoints)));
+    assert_eq!(gesture_result.centroid, Some(Point::new(0.0, 0.0)));
+    daemon.stop();
+}
+
+#[test]
+fn test_candidates_rank_gestures_by_the_rules_they_passed() {
+    for (duration, points) in [
+        (200, straight_swipe((300.0, -120.0))),
+        (100, vec![Point::new(1.0, 2.0)]),
+        (300, multi_touch(&[((0.0, 0.0), (150.0, 0.0)), ((400.0, 0.0), (250.0, 0.0))])),
+    ] {
+        let mut detection = GestureDetection::new(duration, points.clone(), None);
+        let (generic_gesture_type, _) = determine_gesture_rule_based(&mut points.clone(), &mut detection);
+        let candidates = detection.candidates();
+        assert_eq!(candidates.len(), GenericGestureType::values().len());
+        assert_eq!(candidates[0].0, generic_gesture_type);
+        assert!(candidates.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        for (gesture, score) in candidates {
+            let possible = detection.possible_gestures & gesture.java_ordinal() != 0;
+            assert_eq!(possible, score > 0.5, "{} scored {}", gesture.name(), score);
+            assert!((0.0..=1.0).contains(&score));
+        }
+    }
+    // A tap that lasted a quarter of the hold threshold is a click, but not a certain one
+    let mut detection = GestureDetection::new(100, vec![Point::new(1.0, 2.0)], None);
+    determine_gesture_rule_based(&mut vec![Point::new(1.0, 2.0)], &mut detection);
+    assert_eq!(detection.candidates()[..2], [(GenericGestureType::Click, (1.0 + 1.0 + 0.875) / 3.0), (GenericGestureType::Hold, 0.125)]);
+
+    let candidates = [(GenericGestureType::Circle, 1.0), (GenericGestureType::Boomerang, 0.75)];
+    let entries = encode_candidates(&candidates);
+    assert_eq!(entries[2..], [CandidateEntry::EMPTY; CANDIDATE_COUNT - 2]);
+    assert_eq!(decode_candidates(&entries), Ok(candidates.to_vec()));
+    let mut entries = encode_candidates(&[(GenericGestureType::Swipe, 0.5); CANDIDATE_COUNT + 1]);
+    assert_eq!(decode_candidates(&entries).unwrap().len(), CANDIDATE_COUNT);
+    entries[1].gesture_type = 42;
+    assert!(decode_candidates(&entries).is_err());
+}
+
+#[test]
+fn test_candidates_rank_surviving_gestures_by_their_margins() {
+    use GenericGestureType::*;
+    let bits = |gestures: &[GenericGestureType]| gestures.iter().fold(0, |bits, gesture| bits | gesture.java_ordinal());
+    let mut detection = GestureDetection::new(300, Vec::new(), None);
+    detection.remove(bits(&[Pinch, Spread, Rotate, MultiSwipe]), Rule::PointerCount, 1.0, 2.0);
+    detection.remove(bits(&[Click, Hold]), Rule::DragLength, 300.0, 100.0);
+    detection.remove(bits(&[HoldDrag]), Rule::HoldBeforeDrag, 40.0, 400.0);
+    // The return leg only just retraced the outbound leg, which tells a boomerang from a circle
+    detection.remove(bits(&[Circle]), Rule::BoomerangReturn, 18.0, 20.0);
+    assert_eq!(detection.possible_gestures, bits(&[Swipe, Boomerang]));
+
+    let (swipe, boomerang) = (detection.score(Swipe), detection.score(Boomerang));
+    assert!((swipe - (1.0 + 1.0 + 0.95) / 3.0).abs() < 1e-9, "swipe scored {}", swipe);
+    assert!((boomerang - (1.0 + 1.0 + 0.95 + 0.55) / 4.0).abs() < 1e-9, "boomerang scored {}", boomerang);
+    let candidates = detection.candidates();
+    assert_eq!(candidates[..2], [(Swipe, swipe), (Boomerang, boomerang)]);
+    // The result is still the most specific gesture left
+    assert_eq!(detection.result().0, Boomerang);
+    // The circle was ruled out narrowly, the hold-then-drag clearly
+    assert!((detection.score(Circle) - 0.45).abs() < 1e-9);
+    assert!((detection.score(HoldDrag) - 0.05).abs() < 1e-9);
+    assert!(candidates[2..].iter().all(|&(_, score)| score < 0.5));
+}
+
+#[test]
+fn test_candidates_round_trip_through_the_daemon() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_candidates_test_{}", std::process::id()));
+    assert_eq!(daemon.candidates(), Ok(Vec::new()));
+
+    let swipe = straight_swipe((300.0, -120.0));
+    daemon.detect(200, -1, swipe.clone());
+    let mut detection = GestureDetection::new(200, swipe.clone(), None);
+    determine_gesture_rule_based(&mut swipe.clone(), &mut detection);
+    let candidates = daemon.candidates().unwrap();
+    assert_eq!(candidates, detection.candidates()[..CANDIDATE_COUNT]);
+    assert_eq!(candidates[0], (GenericGestureType::Swipe, 1.0));
+
+    assert_ne!(daemon.begin_stream(), -1);
+    daemon.send(ClientCommand::Collect { point: Point::new(0.0, 0.0) });
+    daemon.send(ClientCommand::Detect { duration: 100, count: 2, fling_direction: None });
+    assert_eq!(daemon.candidates(), Ok(Vec::new()));
+    daemon.stop();
+}
+
+/// Reports a swipe to the West although one of the two rules it applies narrowly ruled it out,
+/// so that the swipe scores 0.45.
+#[derive(Debug)]
+struct HedgingDetector;
+
+impl GestureDetector for HedgingDetector {
+    fn name(&self) -> String {
+        String::from("HEDGING")
+    }
+
+    fn detect(
+        &self,
+        _touch_points: &mut Vec<Point>,
+        gesture_detection: &mut GestureDetection,
+    ) -> (GenericGestureType, Option<DirectionResult>) {
+        gesture_detection.remove(GenericGestureType::Swipe.java_ordinal(), Rule::DragLength, 90.0, 100.0);
+        gesture_detection.remove(GenericGestureType::Click.java_ordinal(), Rule::DragLength, 90.0, 10.0);
+        gesture_detection.direction_result = DirectionResult::Drag(Direction::West);
+        (GenericGestureType::Swipe, Some(gesture_detection.direction_result))
+    }
+}
+
+#[test]
+fn test_result_confidence_is_the_score_of_the_reported_gesture() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_confidence_test_{}", std::process::id()));
+    let swipe = straight_swipe((300.0, -120.0));
+    daemon.detect(200, -1, swipe.clone());
+    assert_eq!(daemon.result_record().unwrap().confidence, 1.0);
+
+    let hedging = daemon.register_detector(Arc::new(HedgingDetector));
+    assert_eq!(daemon.configure(ConfigurationBlock { detector: hedging as i64, ..daemon.configuration() }), 0);
+    daemon.detect(200, -1, swipe);
+    let record = daemon.result_record().unwrap();
+    assert_eq!(record.gesture, Some(GenericGestureType::Swipe));
+    assert!((record.confidence - 0.45).abs() < 1e-9, "confidence {}", record.confidence);
+    daemon.stop();
+}
+
+#[test]
+fn test_disqualifications_record_what_each_rule_measured() {
+    let swipe = straight_swipe((300.0, -120.0))
+        .into_iter()
+        .enumerate()
+        .map(|(i, point)| Point::sampled(point.x, point.y, Some(20.0 * i as f64), None))
+        .collect::<Vec<_>>();
+    let mut detection = GestureDetection::new(200, swipe.clone(), None);
+    determine_gesture_rule_based(&mut swipe.clone(), &mut detection);
+    let ruled_out = detection.disqualifications.iter().fold(0, |ruled_out, disqualification| ruled_out | disqualification.gestures);
+    assert_eq!(ruled_out, ALL_GESTURE_TYPES_POSSIBLE & !detection.possible_gestures);
+    assert_eq!(
+        detection.disqualifications[..2],
+        [
+            Disqualification {
+                rule: Rule::PointerCount,
+                measured: 1.0,
+                threshold: 2.0,
+                gestures: GenericGestureType::Pinch.java_ordinal()
+                    | GenericGestureType::Spread.java_ordinal()
+                    | GenericGestureType::Rotate.java_ordinal()
+                    | GenericGestureType::MultiSwipe.java_ordinal(),
+            },
+            Disqualification {
+                rule: Rule::DragLength,
+                measured: 300f64.hypot(120.0),
+                threshold: 100.0,
+                gestures: GenericGestureType::Click.java_ordinal() | GenericGestureType::Hold.java_ordinal(),
+            },
+        ]
+    );
+    assert_eq!(
+        detection.disqualifications[1].gesture_types(),
+        vec![GenericGestureType::Click, GenericGestureType::Hold]
+    );
+
+    // The list starts over with every point of a stream
+    let mut streamed = GestureDetection::new(0, Vec::new(), None);
+    for point in swipe {
+        streamed.add_point(point, &RuleBasedDetector);
+    }
+    assert_eq!(streamed.disqualifications, detection.disqualifications);
+
+    for disqualification in &detection.disqualifications {
+        assert_eq!(DisqualificationEntry::new(disqualification).disqualification(), Ok(*disqualification));
+    }
+    for rule in Rule::values() {
+        assert_eq!(Rule::from_java_ordinal(rule.java_ordinal()), Some(rule));
+    }
+    let entry = DisqualificationEntry::new(&detection.disqualifications[0]);
+    assert!(DisqualificationEntry { rule: 13, ..entry }.disqualification().is_err());
+    assert!(DisqualificationEntry { gestures: -1, ..entry }.disqualification().is_err());
+}
+
+#[test]
+fn test_disqualifications_are_listed_over_ipc() {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_disqualification_test_{}", std::process::id()));
+    assert_eq!(daemon.disqualifications(), Ok(Vec::new()));
+
+    let pinch = multi_touch(&[((0.0, 0.0), (150.0, 0.0)), ((400.0, 0.0), (250.0, 0.0))]);
+    daemon.detect(300, -1, pinch.clone());
+    let mut detection = GestureDetection::new(300, pinch.clone(), None);
+    determine_gesture_rule_based(&mut pinch.clone(), &mut detection);
+    let disqualifications = daemon.disqualifications().unwrap();
+    assert_eq!(disqualifications, detection.disqualifications);
+    assert_eq!(disqualifications.last().map(|disqualification| disqualification.rule), Some(Rule::ScaleFactor));
+    // Listing does not change the result of the detection
+    assert_eq!(daemon.result_record().unwrap().gesture, Some(GenericGestureType::Pinch));
+
+    assert_ne!(daemon.begin_stream(), -1);
+    daemon.send(ClientCommand::Collect { point: Point::new(0.0, 0.0) });
+    daemon.send(ClientCommand::Detect { duration: 100, count: 2, fling_direction: None });
+    assert_eq!(daemon.disqualifications(), Ok(Vec::new()));
+    daemon.stop();
+}
+
+#[test]
+fn test_shared_memory_segment_is_shared_between_mappings() {
+    let name = format!("/gesture_detection_test_{}", std::process::id());
+    let server_segment = SharedMemorySegment::create(&name).unwrap();
+    let client_segment = SharedMemorySegment::open(&name).unwrap();
+    assert_ne!(server_segment.as_ptr(), client_segment.as_ptr());
+    assert_eq!(client_segment.len(), SHARED_MEMORY_SIZE);
+    assert_eq!(client_segment.is_alive.load(Ordering::Acquire), IS_ALIVE);
+
+    server_segment.server_state.store(4, Ordering::Release);
+    assert_eq!(client_segment.server_state.load(Ordering::Acquire), 4);
+
+    drop(client_segment);
+    drop(server_segment);
+    assert!(SharedMemorySegment::open(&name).is_err());
+}
+
+#[test]
+fn test_header_validation() {
+    let header = GestureDetectionIPCHeader::new(88, 1024, 58);
+    assert_eq!(header.validate(88, 1024, 16), Ok(()));
+    assert_eq!(header.validate(96, 1024, 16), Err(HeaderError::HeaderLengthMismatch { expected: 96, found: 88 }));
+    assert_eq!(header.validate(88, 512, 16), Err(HeaderError::SegmentTooSmall { segment_length: 1024, mapped_length: 512 }));
+    assert_eq!(header.validate(88, 1024, 32), Err(HeaderError::PointCapacityTooLarge { point_capacity: 58, available: 29 }));
+
+    let mut bad_magic = header;
+    bad_magic.magic = 0;
+    assert_eq!(bad_magic.validate(88, 1024, 16), Err(HeaderError::BadMagic { found: 0 }));
+    let mut newer_version = header;
+    newer_version.protocol_version = PROTOCOL_VERSION + 1;
+    assert_eq!(newer_version.validate(88, 1024, 16), Err(HeaderError::UnsupportedVersion { found: PROTOCOL_VERSION + 1 }));
+}
+
+#[test]
+fn test_mismatched_protocol_version_is_rejected_on_attach() {
+    let name = format!("/gesture_detection_version_test_{}", std::process::id());
+    let segment = SharedMemorySegment::create(&name).unwrap();
+    assert!(CompositeGestureDetectionClient::try_attach_with_doorbell(&name, Doorbell::Futex).is_ok());
+
+    // Pretend the server was built for a newer protocol
+    unsafe { (segment.as_ptr().add(4) as *mut u32).write(PROTOCOL_VERSION + 1) };
+    let error = CompositeGestureDetectionClient::try_attach_with_doorbell(&name, Doorbell::Futex).unwrap_err();
+    assert!(error.contains("protocol version"), "{}", error);
+}
+
+#[test]
+fn test_generated_layout_files_are_up_to_date() {
+    let stale_files = GestureDetectionIPCBuffer::layout().stale_files(Path::new("layout"));
+    assert!(stale_files.is_empty(), "{:?} out of date, run `cargo run -- --generate-layout`", stale_files);
+}
@@ -0,0 +1,59 @@
+/// How long a touch that does not move has to last to be a hold rather than a click, in milliseconds.
+pub(crate) const DEFAULT_MINIMUM_HOLD_GESTURE_DURATION_THRESHOLD: i128 = 400;
+/// A stroke has to circle its centroid at this mean distance, in dp, to be a circle.
+pub(crate) const DEFAULT_MINIMUM_CIRCLE_RADIUS: f64 = 30f64;
+/// A touch has to move at least this far, in dp, to be anything but a click or a hold.
+pub(crate) const DEFAULT_MINIMUM_DRAG_LENGTH: f64 = 100f64;
+
+/// The thresholds the rule-based and multi-touch detectors tell clicks, holds, drags and circles
+/// apart by, with the distances in dp of the `Display`. Devices with a different screen size or
+/// input method need different values, so clients can change them with CONFIGURE.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Thresholds {
+    hold_duration: i128,
+    circle_radius: f64,
+    drag_length: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            hold_duration: DEFAULT_MINIMUM_HOLD_GESTURE_DURATION_THRESHOLD,
+            circle_radius: DEFAULT_MINIMUM_CIRCLE_RADIUS,
+            drag_length: DEFAULT_MINIMUM_DRAG_LENGTH,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Fails unless every threshold is positive, and finite for the distances.
+    pub(crate) fn new(hold_duration: i128, circle_radius: f64, drag_length: f64) -> Result<Self, String> {
+        if hold_duration <= 0 {
+            return Err(format!("Hold duration {}ms is not positive", hold_duration));
+        }
+        if !(circle_radius > 0.0 && circle_radius.is_finite()) {
+            return Err(format!("Circle radius {} is not a positive number of dp", circle_radius));
+        }
+        if !(drag_length > 0.0 && drag_length.is_finite()) {
+            return Err(format!("Drag length {} is not a positive number of dp", drag_length));
+        }
+        Ok(Thresholds { hold_duration, circle_radius, drag_length })
+    }
+
+    /// Milliseconds a touch has to last to be a hold, or to stay still before dragging to be a
+    /// hold-then-drag.
+    pub(crate) fn hold_duration(&self) -> i128 {
+        self.hold_duration
+    }
+
+    /// Density-independent pixels a stroke has to circle its centroid at to be a circle.
+    pub(crate) fn circle_radius(&self) -> f64 {
+        self.circle_radius
+    }
+
+    /// Density-independent pixels a touch has to move from its first point to be a drag, and that every finger of a
+    /// multi-finger swipe has to move.
+    pub(crate) fn drag_length(&self) -> f64 {
+        self.drag_length
+    }
+}
@@ -2,15 +2,78 @@
 mod core;
 mod build;
 
+use std::env;
+use std::path::Path;
 use crate::core::detection_system::{GestureDetectionDaemon, GestureDetectionIPCBuffer};
+use crate::core::point::Point;
+
+/// Directory the shared memory layout files are generated into by default.
+const LAYOUT_DIRECTORY: &str = "layout";
 
 /// Main function demonstrating how to use the `GestureQueue`.
+/// Run with `--generate-layout [directory]` to regenerate the layout files for other clients instead,
+/// or with `--explain duration x,y[,t] ...` to see why the given points are detected as they are.
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(index) = args.iter().position(|arg| arg == "--generate-layout") {
+        let directory = args.get(index + 1).map_or(LAYOUT_DIRECTORY, |directory| directory.as_str());
+        GestureDetectionIPCBuffer::layout()
+            .write_files(Path::new(directory))
+            .expect("Failed to write the layout files");
+        println!("Wrote the shared memory layout to {}", directory);
+        return;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--explain") {
+        match parse_gesture(&args[index + 1..]) {
+            Ok((duration, points)) => explain(duration, points),
+            Err(error) => println!("{}\nUsage: --explain <duration in ms> <x,y[,timestamp in ms]>...", error),
+        }
+        return;
+    }
+
     let daemon = GestureDetectionDaemon::new();
     println!("buffer: {:?}", daemon);
 
     let result = daemon.detect(1000, 0, vec![core::point::Point::new(1.0, 2.0), core::point::Point::new(3.0, 4.0)]);
 }
 
+/// The duration and points of a gesture given on the command line.
+fn parse_gesture(args: &[String]) -> Result<(i128, Vec<Point>), String> {
+    let (duration, points) = args.split_first().ok_or("Missing the duration")?;
+    let duration = duration.parse::<i128>().map_err(|_| format!("Invalid duration {}", duration))?;
+    let points = points
+        .iter()
+        .map(|point| {
+            let fields = point
+                .split(',')
+                .map(|field| field.parse::<f64>().map_err(|_| format!("Invalid point {}", point)))
+                .collect::<Result<Vec<_>, _>>()?;
+            match fields[..] {
+                [x, y] => Ok(Point::new(x, y)),
+                [x, y, timestamp] => Ok(Point::sampled(x, y, Some(timestamp), None)),
+                _ => Err(format!("Invalid point {}", point)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((duration, points))
+}
+
+/// Detect a gesture with a daemon of its own and print the rules that ruled the other gestures out.
+/// The daemon serves a segment named after this process, so that a service already running on
+/// the default segment is left alone, and starts without the saved templates.
+fn explain(duration: i128, points: Vec<Point>) {
+    let mut daemon = GestureDetectionDaemon::with_name(&format!("/gesture_detection_explain_{}", std::process::id()));
+    daemon.detect(duration, -1, points);
+    match daemon.result_record() {
+        Ok(gesture_result) => println!("Detected {:?}", gesture_result.gesture.map(|gesture| gesture.name())),
+        Err(error) => println!("Unreadable result: {}", error),
+    }
+    match daemon.disqualifications() {
+        Ok(disqualifications) => disqualifications.iter().for_each(|disqualification| println!("  {}", disqualification)),
+        Err(error) => println!("Could not list the disqualifications: {}", error),
+    }
+    daemon.stop();
+}
+
 
 